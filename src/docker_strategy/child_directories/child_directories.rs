@@ -1,27 +1,91 @@
-use std::{any::Any, fmt::Debug};
+use std::{any::Any, fmt::Debug, path::Path};
 
-use crate::docker_strategy::{
-    containers::container::Container, parent_directories::ParentDirectories,
+use crate::{
+    docker_strategy::{
+        containers::container::Container, file_handle::FileHandle, images::Image,
+        networks::Network, parent_directories::ParentDirectories, volumes::Volume,
+    },
+    fuse_handler::DirectoryEntry,
 };
 
 use fuser::FileAttr;
+use futures::future::BoxFuture;
 
-pub trait ChildDirectory: Debug + Any {
+pub trait ChildDirectory: Debug + Any + Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn get_ino(&self) -> u64;
     fn get_id(&self) -> &String;
     fn get_name(&self) -> &String;
     fn get_parent(&self) -> ParentDirectories;
 
-    fn try_into_container(self: Box<Self>) -> Option<Container> {
-        match self.get_parent() {
-            _ => None,
-        }
+    // Objects rebuilt on a refresh share the state of the child directory they replace.
+    fn as_container(&self) -> Option<&Container> {
+        None
     }
 
-    fn read_dir(&self, offset: i64, reply: &mut fuser::ReplyDirectory) -> Result<(), libc::c_int>;
-    fn lookup(&self, name: &str) -> Result<(), libc::c_int>;
-    fn getattr(&self) -> FileAttr;
+    fn as_image(&self) -> Option<&Image> {
+        None
+    }
+
+    fn as_volume(&self) -> Option<&Volume> {
+        None
+    }
+
+    fn as_network(&self) -> Option<&Network> {
+        None
+    }
+
+    // `path` is relative to the child directory itself, the empty string being the directory.
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>>;
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>>;
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int>;
+    fn set_size(&self, path: &str, size: u64) -> Result<FileAttr, libc::c_int>;
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>>;
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int>;
+
+    // Only volume data and network members can be changed, other directories keeping their
+    // fixed entries.
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        self.getattr(path)?;
+        Err(libc::EPERM)
+    }
+
+    fn create(&self, _path: &str) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn mkdir(&self, _path: &str) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EPERM)
+    }
+
+    fn rename(&self, _path: &str, _new_path: &str) -> Result<(), libc::c_int> {
+        Err(libc::EPERM)
+    }
+
+    fn symlink(&self, _path: &str, _link: &Path) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EPERM)
+    }
+}
+
+pub fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", path, name)
+    }
 }
 
 // pub enum ChildDirectories {
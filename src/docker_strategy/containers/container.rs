@@ -1,10 +1,23 @@
-use std::time::{Duration, UNIX_EPOCH};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, UNIX_EPOCH},
+};
 
 use bollard::service::ContainerSummary;
 use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
 
-use crate::docker_strategy::{
-    child_directories::child_directories::ChildDirectory, parent_directories::ParentDirectories,
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        containers::{ContainerFile, LogStream, RootFs},
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
 };
 
 #[derive(Debug)]
@@ -12,8 +25,14 @@ pub struct Container {
     ino: u64,
     name: String,
     pub container: ContainerSummary,
+    log_streams: Arc<Mutex<HashMap<&'static str, Weak<LogStream>>>>,
+    rootfs: Arc<Mutex<Option<Arc<RootFs>>>>,
+    inodes: Inodes,
 }
 
+const ROOTFS: &str = "rootfs";
+const NETWORKS: &str = "networks";
+
 impl From<Container> for Box<dyn ChildDirectory> {
     fn from(value: Container) -> Self {
         Box::new(value)
@@ -41,63 +60,192 @@ impl ChildDirectory for Container {
         ParentDirectories::Containers
     }
 
-    fn read_dir(&self, offset: i64, reply: &mut fuser::ReplyDirectory) -> Result<(), libc::c_int> {
-        log::debug!("containers_read_dir(offset: {})", offset);
+    fn as_container(&self) -> Option<&Container> {
+        Some(self)
+    }
 
-        let entries = vec![
-            (self.ino, String::from(".")),
-            (ParentDirectories::Containers as u64, String::from("..")),
-        ];
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            log::debug!("containers_read_dir(path: {})", path);
 
-        for (i, (ino, name)) in entries.iter().enumerate().skip(offset as usize) {
-            log::debug!("containers_read_dir: {} {} {}", i, ino, name);
-            if reply.add(*ino, i as i64 + 1, FileType::Directory, name) {
-                break;
+            match Self::rootfs_path(path) {
+                Some(rootfs_path) => self.rootfs_entries(path, rootfs_path, docker).await,
+                None if path.is_empty() => Ok(self.entries()),
+                None if path == NETWORKS => Ok(self.network_entries()),
+                None => Err(libc::ENOTDIR),
             }
         }
+        .boxed()
+    }
 
-        Ok(())
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            let path = join_path(path, name);
+
+            if let Some(rootfs_path) = Self::rootfs_path(&path) {
+                if !rootfs_path.is_empty() {
+                    self.rootfs(docker).await?;
+                }
+            }
+
+            self.getattr(&path)
+        }
+        .boxed()
     }
 
-    fn lookup(&self, name: &str) -> Result<(), libc::c_int> {
-        todo!()
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        if path.is_empty() {
+            return Ok(self.dir_attr());
+        }
+
+        match Self::rootfs_path(path) {
+            Some("") => {
+                return Ok(FileAttr {
+                    ino: self.entry_ino(path),
+                    ..self.dir_attr()
+                })
+            }
+            Some(rootfs_path) => {
+                let rootfs = self.rootfs.lock().unwrap().clone().ok_or(libc::ENOENT)?;
+                let node = rootfs.get(rootfs_path).ok_or(libc::ENOENT)?;
+                return Ok(node.attr(self.entry_ino(path)));
+            }
+            None => (),
+        }
+
+        match path.split_once('/') {
+            None if path == NETWORKS => {
+                return Ok(FileAttr {
+                    ino: self.entry_ino(path),
+                    ..self.dir_attr()
+                })
+            }
+            Some((NETWORKS, network)) if self.networks().any(|name| name == network) => {
+                return Ok(FileAttr {
+                    ino: self.entry_ino(path),
+                    size: Self::network_link(network).len() as u64,
+                    kind: FileType::Symlink,
+                    perm: 0o777,
+                    nlink: 1,
+                    ..self.dir_attr()
+                })
+            }
+            Some((NETWORKS, _)) => return Err(libc::ENOENT),
+            _ => (),
+        }
+
+        let file = ContainerFile::try_from(path).map_err(|_| libc::ENOENT)?;
+
+        Ok(FileAttr {
+            ino: self.file_ino(file),
+            size: match self.file_content(file) {
+                Some(content) => content.len() as u64,
+                None => self
+                    .log_streams
+                    .lock()
+                    .unwrap()
+                    .get(file.as_str())
+                    .and_then(Weak::upgrade)
+                    .map(|stream| stream.len())
+                    .unwrap_or_default(),
+            },
+            kind: FileType::RegularFile,
+            perm: file.perm(),
+            nlink: 1,
+            ..self.dir_attr()
+        })
     }
 
-    fn getattr(&self) -> FileAttr {
-        let time =
-            UNIX_EPOCH + Duration::from_secs(self.container.created.unwrap_or_default() as u64);
+    // Only the control file can be truncated, as shells do when redirecting into it.
+    fn set_size(&self, path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        match ContainerFile::try_from(path) {
+            Ok(ContainerFile::Ctl) => self.getattr(path),
+            _ => Err(libc::EACCES),
+        }
+    }
 
-        FileAttr {
-            ino: self.ino,
-            size: 0,
-            blocks: 0,
-            atime: UNIX_EPOCH,
-            mtime: time,
-            ctime: time,
-            crtime: time,
-            kind: FileType::Directory,
-            perm: 0o755,
-            nlink: 0,
-            uid: 0,
-            gid: 0,
-            rdev: 0,
-            flags: 0,
-            blksize: 0,
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            if let Some(rootfs_path) = Self::rootfs_path(path) {
+                let rootfs = self.rootfs(docker).await?;
+                let content = rootfs.content(docker, self.get_id(), rootfs_path).await?;
+                return Ok(FileHandle::Snapshot(content));
+            }
+
+            let file = ContainerFile::try_from(path).map_err(|_| libc::ENOENT)?;
+
+            if let Some(content) = self.file_content(file) {
+                return Ok(FileHandle::Snapshot(Arc::new(content.into_bytes())));
+            }
+
+            match file {
+                ContainerFile::Logs | ContainerFile::Stdout | ContainerFile::Stderr => {
+                    Ok(FileHandle::Logs {
+                        stream: self.log_stream(file, docker).await?,
+                        last_eof: Mutex::new(None),
+                    })
+                }
+                ContainerFile::Ctl => Ok(FileHandle::Control {
+                    docker: docker.clone(),
+                    id: self.get_id().clone(),
+                }),
+                _ => Ok(FileHandle::Snapshot(Arc::new(self.inspect(docker).await?))),
+            }
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        if let Some((NETWORKS, network)) = path.split_once('/') {
+            self.getattr(path)?;
+            return Ok(Self::network_link(network));
         }
+
+        let rootfs_path = Self::rootfs_path(path).ok_or(libc::EINVAL)?;
+        let rootfs = self.rootfs.lock().unwrap().clone().ok_or(libc::ENOENT)?;
+
+        rootfs
+            .get(rootfs_path)
+            .ok_or(libc::ENOENT)?
+            .link
+            .clone()
+            .ok_or(libc::EINVAL)
     }
 }
 
 impl Container {
-    pub fn new(container: ContainerSummary) -> Self {
+    pub fn new(container: ContainerSummary, inodes: &Inodes) -> Self {
+        // Docker names start with a `/` and list links as `/<container>/<alias>`, podman names
+        // have no `/` at all.
         let names: Vec<String> = container
             .names
             .unwrap_or_default()
             .into_iter()
             .map(|name| name.trim_start_matches('/').to_string())
+            .filter(|name| !name.is_empty())
             .collect();
+        let name = names
+            .iter()
+            .find(|name| !name.contains('/'))
+            .or(names.first())
+            .cloned()
+            .unwrap_or_else(|| container.id.clone().unwrap_or_default());
 
         let ino = if let Some(id) = container.id.as_ref() {
-            ParentDirectories::ino_from_docker_id(id)
+            inodes.lock().unwrap().pin(InodeKind::Container, id)
         } else {
             panic!("Container without id")
         };
@@ -107,8 +255,223 @@ impl Container {
                 names: Some(names.clone()),
                 ..container
             },
-            name: names.first().unwrap().to_string(),
+            name,
             ino,
+            log_streams: Arc::new(Mutex::new(HashMap::new())),
+            rootfs: Arc::new(Mutex::new(None)),
+            inodes: inodes.clone(),
+        }
+    }
+
+    // Containers are rebuilt on every refresh, log streams still followed and the unpacked
+    // filesystem must survive it.
+    pub fn keep_state(&mut self, previous: &Container) {
+        self.log_streams = previous.log_streams.clone();
+        self.rootfs = previous.rootfs.clone();
+    }
+
+    fn rootfs_path(path: &str) -> Option<&str> {
+        match path.split_once('/') {
+            Some((ROOTFS, rootfs_path)) => Some(rootfs_path),
+            None if path == ROOTFS => Some(""),
+            _ => None,
+        }
+    }
+
+    async fn rootfs(&self, docker: &bollard::Docker) -> Result<Arc<RootFs>, libc::c_int> {
+        if let Some(rootfs) = self.rootfs.lock().unwrap().as_ref() {
+            if !rootfs.is_expired() {
+                return Ok(rootfs.clone());
+            }
+        }
+
+        let rootfs = Arc::new(RootFs::load(docker, self.get_id()).await?);
+        *self.rootfs.lock().unwrap() = Some(rootfs.clone());
+
+        Ok(rootfs)
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.ino, FileType::Directory, "."),
+            DirectoryEntry::new(
+                ParentDirectories::Containers as u64,
+                FileType::Directory,
+                "..",
+            ),
+        ];
+
+        entries.extend(ContainerFile::iterator().map(|file| {
+            DirectoryEntry::new(self.file_ino(file), FileType::RegularFile, file.as_str())
+        }));
+
+        entries.push(DirectoryEntry::new(
+            self.entry_ino(ROOTFS),
+            FileType::Directory,
+            ROOTFS,
+        ));
+        entries.push(DirectoryEntry::new(
+            self.entry_ino(NETWORKS),
+            FileType::Directory,
+            NETWORKS,
+        ));
+
+        entries
+    }
+
+    // Networks the container is connected to, as of the last refresh of containers.
+    fn networks(&self) -> impl Iterator<Item = &String> {
+        let mut networks: Vec<&String> = self
+            .container
+            .network_settings
+            .iter()
+            .flat_map(|settings| settings.networks.iter().flatten())
+            .map(|(name, _)| name)
+            .collect();
+        networks.sort();
+
+        networks.into_iter()
+    }
+
+    fn network_entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.entry_ino(NETWORKS), FileType::Directory, "."),
+            DirectoryEntry::new(self.ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(self.networks().map(|network| {
+            DirectoryEntry::new(
+                self.entry_ino(&join_path(NETWORKS, network)),
+                FileType::Symlink,
+                network,
+            )
+        }));
+
+        entries
+    }
+
+    // From `/containers/<name>/networks/<network>` to `/networks/<network>`.
+    fn network_link(network: &str) -> String {
+        format!(
+            "../../../{}/{}",
+            ParentDirectories::Networks.to_string(),
+            network
+        )
+    }
+
+    async fn rootfs_entries(
+        &self,
+        path: &str,
+        rootfs_path: &str,
+        docker: &bollard::Docker,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let rootfs = self.rootfs(docker).await?;
+        let node = rootfs.get(rootfs_path).ok_or(libc::ENOENT)?;
+        if node.kind != FileType::Directory {
+            return Err(libc::ENOTDIR);
+        }
+
+        let parent_ino = match path.rsplit_once('/') {
+            Some((parent, _)) => self.entry_ino(parent),
+            None => self.ino,
+        };
+
+        let mut entries = vec![
+            DirectoryEntry::new(self.entry_ino(path), FileType::Directory, "."),
+            DirectoryEntry::new(parent_ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(node.children.iter().map(|(name, kind)| {
+            DirectoryEntry::new(self.entry_ino(&join_path(path, name)), *kind, name)
+        }));
+
+        Ok(entries)
+    }
+
+    async fn log_stream(
+        &self,
+        file: ContainerFile,
+        docker: &bollard::Docker,
+    ) -> Result<Arc<LogStream>, libc::c_int> {
+        let stream = self
+            .log_streams
+            .lock()
+            .unwrap()
+            .get(file.as_str())
+            .and_then(Weak::upgrade);
+
+        if let Some(stream) = stream {
+            return Ok(stream);
+        }
+
+        let (stdout, stderr) = match file {
+            ContainerFile::Stdout => (true, false),
+            ContainerFile::Stderr => (false, true),
+            _ => (true, true),
+        };
+
+        let stream = Arc::new(LogStream::open(docker, self.get_id(), stdout, stderr).await?);
+        self.log_streams
+            .lock()
+            .unwrap()
+            .insert(file.as_str(), Arc::downgrade(&stream));
+
+        Ok(stream)
+    }
+
+    async fn inspect(&self, docker: &bollard::Docker) -> Result<Vec<u8>, libc::c_int> {
+        let inspect = docker
+            .inspect_container(self.get_id(), None)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to inspect container {}: {}", self.name, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        let mut content = serde_json::to_vec_pretty(&inspect).map_err(|e| {
+            log::error!("Failed to serialize container {}: {}", self.name, e);
+            libc::EIO
+        })?;
+        content.push(b'\n');
+
+        Ok(content)
+    }
+
+    fn file_ino(&self, file: ContainerFile) -> u64 {
+        self.entry_ino(file.as_str())
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Container, self.get_id(), path)
+    }
+
+    fn file_content(&self, file: ContainerFile) -> Option<String> {
+        file.content(&self.name, &self.container)
+    }
+
+    fn dir_attr(&self) -> FileAttr {
+        let time =
+            UNIX_EPOCH + Duration::from_secs(self.container.created.unwrap_or_default() as u64);
+
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
         }
     }
 }
@@ -0,0 +1,104 @@
+use bollard::container::{KillContainerOptions, StartContainerOptions};
+
+use crate::docker_strategy::{docker::errno_from_docker_error, docker_strategy::DockerError};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ContainerCommand {
+    Start,
+    Stop,
+    Restart,
+    Kill,
+    Pause,
+    Unpause,
+}
+
+impl TryFrom<&str> for ContainerCommand {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "start" => Ok(ContainerCommand::Start),
+            "stop" => Ok(ContainerCommand::Stop),
+            "restart" => Ok(ContainerCommand::Restart),
+            "kill" => Ok(ContainerCommand::Kill),
+            "pause" => Ok(ContainerCommand::Pause),
+            "unpause" => Ok(ContainerCommand::Unpause),
+            _ => Err(DockerError::InvalidCommand),
+        }
+    }
+}
+
+impl ContainerCommand {
+    pub async fn run(&self, docker: &bollard::Docker, id: &str) -> Result<(), libc::c_int> {
+        let result = match self {
+            ContainerCommand::Start => {
+                docker
+                    .start_container(id, None::<StartContainerOptions<String>>)
+                    .await
+            }
+            ContainerCommand::Stop => docker.stop_container(id, None).await,
+            ContainerCommand::Restart => docker.restart_container(id, None).await,
+            ContainerCommand::Kill => {
+                docker
+                    .kill_container(id, None::<KillContainerOptions<String>>)
+                    .await
+            }
+            ContainerCommand::Pause => docker.pause_container(id).await,
+            ContainerCommand::Unpause => docker.unpause_container(id).await,
+        };
+
+        result.map_err(|e| {
+            log::error!("Failed to {:?} container {}: {}", self, id, e);
+            errno_from_docker_error(&e)
+        })
+    }
+
+    // Every non empty line written to the control file is a command, run in order.
+    pub async fn run_all(
+        docker: &bollard::Docker,
+        id: &str,
+        data: &[u8],
+    ) -> Result<(), libc::c_int> {
+        let commands = std::str::from_utf8(data).map_err(|_| libc::EINVAL)?;
+
+        for command in commands
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            ContainerCommand::try_from(command)
+                .map_err(|_| libc::EINVAL)?
+                .run(docker, id)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let parse = |command| ContainerCommand::try_from(command);
+
+        assert!(matches!(parse("start"), Ok(ContainerCommand::Start)));
+        assert!(matches!(parse("stop"), Ok(ContainerCommand::Stop)));
+        assert!(matches!(parse("restart"), Ok(ContainerCommand::Restart)));
+        assert!(matches!(parse("kill"), Ok(ContainerCommand::Kill)));
+        assert!(matches!(parse("pause"), Ok(ContainerCommand::Pause)));
+        assert!(matches!(parse("unpause"), Ok(ContainerCommand::Unpause)));
+    }
+
+    #[test]
+    fn test_parse_invalid_commands() {
+        for command in ["", "Start", "start now", "create", "rm"] {
+            assert!(matches!(
+                ContainerCommand::try_from(command),
+                Err(DockerError::InvalidCommand)
+            ));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use bollard::service::ContainerSummary;
+
+use crate::docker_strategy::docker_strategy::DockerError;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ContainerFile {
+    Id,
+    Name,
+    Image,
+    Status,
+    State,
+    Created,
+    Command,
+    Labels,
+    Inspect,
+    Logs,
+    Stdout,
+    Stderr,
+    Ctl,
+}
+
+impl TryFrom<&str> for ContainerFile {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "id" => Ok(ContainerFile::Id),
+            "name" => Ok(ContainerFile::Name),
+            "image" => Ok(ContainerFile::Image),
+            "status" => Ok(ContainerFile::Status),
+            "state" => Ok(ContainerFile::State),
+            "created" => Ok(ContainerFile::Created),
+            "command" => Ok(ContainerFile::Command),
+            "labels" => Ok(ContainerFile::Labels),
+            "inspect.json" => Ok(ContainerFile::Inspect),
+            "logs" => Ok(ContainerFile::Logs),
+            "stdout" => Ok(ContainerFile::Stdout),
+            "stderr" => Ok(ContainerFile::Stderr),
+            "ctl" => Ok(ContainerFile::Ctl),
+            _ => Err(DockerError::UnknownChildEntry),
+        }
+    }
+}
+
+impl ContainerFile {
+    pub fn iterator() -> impl Iterator<Item = ContainerFile> {
+        [
+            ContainerFile::Id,
+            ContainerFile::Name,
+            ContainerFile::Image,
+            ContainerFile::Status,
+            ContainerFile::State,
+            ContainerFile::Created,
+            ContainerFile::Command,
+            ContainerFile::Labels,
+            ContainerFile::Inspect,
+            ContainerFile::Logs,
+            ContainerFile::Stdout,
+            ContainerFile::Stderr,
+            ContainerFile::Ctl,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContainerFile::Id => "id",
+            ContainerFile::Name => "name",
+            ContainerFile::Image => "image",
+            ContainerFile::Status => "status",
+            ContainerFile::State => "state",
+            ContainerFile::Created => "created",
+            ContainerFile::Command => "command",
+            ContainerFile::Labels => "labels",
+            ContainerFile::Inspect => "inspect.json",
+            ContainerFile::Logs => "logs",
+            ContainerFile::Stdout => "stdout",
+            ContainerFile::Stderr => "stderr",
+            ContainerFile::Ctl => "ctl",
+        }
+    }
+
+    pub fn perm(&self) -> u16 {
+        match self {
+            ContainerFile::Ctl => 0o200,
+            _ => 0o444,
+        }
+    }
+
+    // Every file ends with a newline so that `cat` output stays readable in a shell.
+    // Files which aren't generated from the summary are fetched from the daemon on open.
+    pub fn content(&self, name: &str, container: &ContainerSummary) -> Option<String> {
+        let mut content = match self {
+            ContainerFile::Id => container.id.clone().unwrap_or_default(),
+            ContainerFile::Name => name.to_string(),
+            ContainerFile::Image => container.image.clone().unwrap_or_default(),
+            ContainerFile::Status => container.status.clone().unwrap_or_default(),
+            ContainerFile::State => container.state.clone().unwrap_or_default(),
+            ContainerFile::Created => container.created.unwrap_or_default().to_string(),
+            ContainerFile::Command => container.command.clone().unwrap_or_default(),
+            ContainerFile::Labels => {
+                let mut labels: Vec<String> = container
+                    .labels
+                    .iter()
+                    .flatten()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
+                labels.sort();
+                labels.join("\n")
+            }
+            ContainerFile::Inspect
+            | ContainerFile::Logs
+            | ContainerFile::Stdout
+            | ContainerFile::Stderr
+            | ContainerFile::Ctl => return None,
+        };
+
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        Some(content)
+    }
+}
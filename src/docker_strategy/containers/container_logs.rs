@@ -0,0 +1,356 @@
+use std::{
+    future::Future,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use bollard::container::{LogOutput, LogsOptions};
+use futures::{Stream, StreamExt};
+use tokio::task::JoinHandle;
+
+use crate::docker_strategy::docker::errno_from_docker_error;
+
+// Readers waiting for new lines are answered with EOF after a while, so that the kernel isn't
+// left waiting on a reader that is gone.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+type LogResult = Result<LogOutput, bollard::errors::Error>;
+
+// Time of a log line as prefixed by the daemon, in RFC 3339 and UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Timestamp {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    fn parse(text: &str) -> Option<Self> {
+        let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+
+        let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+        let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+        let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+        let nanos = match fraction {
+            "" => 0,
+            fraction => format!("{:0<9}", fraction).get(..9)?.parse().ok()?,
+        };
+
+        // Days since the epoch of a date of the proleptic Gregorian calendar.
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+
+        Some(Self {
+            seconds: days * 86400 + hours * 3600 + minutes * 60 + seconds,
+            nanos,
+        })
+    }
+
+    // Splits the timestamp the daemon prefixes lines with from the line.
+    fn split(message: &[u8]) -> (Option<Self>, &[u8]) {
+        let Some(space) = message.iter().position(|&byte| byte == b' ') else {
+            return (None, message);
+        };
+
+        match std::str::from_utf8(&message[..space])
+            .ok()
+            .and_then(Self::parse)
+        {
+            Some(timestamp) => (Some(timestamp), &message[space + 1..]),
+            None => (None, message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LogBuffer {
+    data: Vec<u8>,
+    finished: bool,
+}
+
+#[derive(Debug, Default)]
+struct SharedLogBuffer {
+    buffer: Mutex<LogBuffer>,
+    condvar: Condvar,
+}
+
+impl SharedLogBuffer {
+    fn push(&self, message: &[u8]) {
+        self.buffer.lock().unwrap().data.extend_from_slice(message);
+        self.condvar.notify_all();
+    }
+
+    fn finish(&self) {
+        self.buffer.lock().unwrap().finished = true;
+        self.condvar.notify_all();
+    }
+}
+
+// Appends to a stream fed by a background task rather than by the logs of a container.
+#[derive(Debug)]
+pub struct LogWriter(Arc<SharedLogBuffer>);
+
+impl LogWriter {
+    pub fn push(&self, message: &[u8]) {
+        self.0.push(message);
+    }
+}
+
+// Log history of a container followed in the background, shared by every open handle on the
+// same log file so that `getattr` can report the size `tail -f` polls for.
+#[derive(Debug)]
+pub struct LogStream {
+    shared: Arc<SharedLogBuffer>,
+    follower: JoinHandle<()>,
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.follower.abort();
+    }
+}
+
+impl LogStream {
+    // The history is fully loaded before returning, so that a reader hitting the end of the
+    // buffer right after open doesn't miss any of it. New lines are then followed from the time
+    // of the last line of the history, lines of that same time being skipped.
+    pub async fn open(
+        docker: &bollard::Docker,
+        id: &str,
+        stdout: bool,
+        stderr: bool,
+    ) -> Result<LogStream, libc::c_int> {
+        let options = move |follow, since| LogsOptions {
+            follow,
+            stdout,
+            stderr,
+            since,
+            timestamps: true,
+            tail: "all".to_string(),
+            ..Default::default()
+        };
+
+        let history = docker.logs(id, Some(options(false, 0)));
+        let (docker, follower_id) = (docker.clone(), id.to_string());
+        Self::start(id, history, move |since| {
+            docker.logs(&follower_id, Some(options(true, since)))
+        })
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch logs of container {}: {}", id, e);
+            errno_from_docker_error(&e)
+        })
+    }
+
+    async fn start<S>(
+        id: &str,
+        history: impl Stream<Item = LogResult>,
+        follow: impl FnOnce(i64) -> S,
+    ) -> Result<LogStream, bollard::errors::Error>
+    where
+        S: Stream<Item = LogResult> + Send + 'static,
+    {
+        let shared = Arc::new(SharedLogBuffer::default());
+        let last = Self::consume(history, &shared, None).await?;
+
+        let logs = follow(last.map_or(0, |last| last.seconds));
+        let follower_shared = shared.clone();
+        let follower_id = id.to_string();
+        let follower = tokio::spawn(async move {
+            if let Err(e) = Self::consume(logs, &follower_shared, last).await {
+                log::debug!("Stopped following logs of container {}: {}", follower_id, e);
+            }
+            follower_shared.finish();
+        });
+
+        Ok(Self { shared, follower })
+    }
+
+    // The stream ends when the task returns, and the task is stopped along with the stream.
+    pub fn spawn<F>(task: impl FnOnce(LogWriter) -> F) -> LogStream
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let shared = Arc::new(SharedLogBuffer::default());
+
+        let task = task(LogWriter(shared.clone()));
+        let follower_shared = shared.clone();
+        let follower = tokio::spawn(async move {
+            task.await;
+            follower_shared.finish();
+        });
+
+        Self { shared, follower }
+    }
+
+    // Appends the lines of a stream until it ends, without the ones up to `after`, and returns
+    // the time of the last line.
+    async fn consume(
+        stream: impl Stream<Item = LogResult>,
+        shared: &SharedLogBuffer,
+        after: Option<Timestamp>,
+    ) -> Result<Option<Timestamp>, bollard::errors::Error> {
+        futures::pin_mut!(stream);
+
+        let mut last = after;
+        while let Some(output) = stream.next().await {
+            let message = output?.into_bytes();
+            let (timestamp, line) = Timestamp::split(&message);
+
+            match timestamp {
+                Some(timestamp) if after.is_some_and(|after| timestamp <= after) => continue,
+                Some(timestamp) => last = Some(timestamp),
+                None => (),
+            }
+            shared.push(line);
+        }
+        Ok(last)
+    }
+
+    // Ends the stream for its readers, the task feeding it being stopped.
+    pub fn stop(&self) {
+        self.follower.abort();
+        self.shared.finish();
+    }
+
+    pub fn len(&self) -> u64 {
+        self.shared.buffer.lock().unwrap().data.len() as u64
+    }
+
+    // Reaching the end of the logs returns EOF once, so that `cat` terminates with the full
+    // history, while reading again at the same offset blocks until new lines are followed, or
+    // returns EOF again after a while.
+    pub fn read(
+        &self,
+        offset: i64,
+        size: u32,
+        last_eof: &Mutex<Option<i64>>,
+    ) -> Result<Vec<u8>, libc::c_int> {
+        let offset = offset.max(0) as usize;
+        let mut buffer = self.shared.buffer.lock().unwrap();
+
+        if buffer.data.len() <= offset && !buffer.finished {
+            let mut last_eof = last_eof.lock().unwrap();
+            if *last_eof != Some(offset as i64) {
+                *last_eof = Some(offset as i64);
+                return Ok(Vec::new());
+            }
+            drop(last_eof);
+
+            buffer = self
+                .shared
+                .condvar
+                .wait_timeout_while(buffer, WAIT_TIMEOUT, |buffer| {
+                    buffer.data.len() <= offset && !buffer.finished
+                })
+                .unwrap()
+                .0;
+        }
+
+        let start = offset.min(buffer.data.len());
+        let end = (start + size as usize).min(buffer.data.len());
+
+        Ok(buffer.data[start..end].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(line: &str) -> LogResult {
+        Ok(LogOutput::StdOut {
+            message: line.to_string().into(),
+        })
+    }
+
+    fn data(stream: &LogStream) -> String {
+        String::from_utf8(stream.shared.buffer.lock().unwrap().data.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_timestamps() {
+        let timestamp = Timestamp::parse("2024-01-01T00:00:00Z").unwrap();
+        assert_eq!((timestamp.seconds, timestamp.nanos), (1704067200, 0));
+
+        let timestamp = Timestamp::parse("2000-03-01T12:30:15.25Z").unwrap();
+        assert_eq!((timestamp.seconds, timestamp.nanos), (951913815, 250000000));
+
+        assert!(
+            Timestamp::parse("2024-01-01T00:00:00.5Z")
+                > Timestamp::parse("2024-01-01T00:00:00.25Z")
+        );
+        assert!(Timestamp::parse("2024-01-01 00:00:00").is_none());
+
+        let (timestamp, line) = Timestamp::split(b"2024-01-01T00:00:00.5Z hello world\n");
+        assert_eq!(timestamp, Timestamp::parse("2024-01-01T00:00:00.5Z"));
+        assert_eq!(line, b"hello world\n");
+        assert_eq!(
+            Timestamp::split(b"hello world\n"),
+            (None, &b"hello world\n"[..])
+        );
+    }
+
+    // The history is complete even with pauses, and a followed stream that never pauses doesn't
+    // keep it from being returned.
+    #[tokio::test]
+    async fn test_history_then_busy_follow() {
+        let history = futures::stream::iter([
+            "2024-01-01T00:00:01.5Z one\n",
+            "2024-01-01T00:00:02.25Z two\n",
+        ])
+        .then(|line| async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            frame(line)
+        });
+
+        let since = Arc::new(Mutex::new(None));
+        let follow_since = since.clone();
+        let follow = move |seconds| {
+            *follow_since.lock().unwrap() = Some(seconds);
+
+            let boundary = futures::stream::iter([
+                frame("2024-01-01T00:00:02.25Z two\n"),
+                frame("2024-01-01T00:00:02.5Z three\n"),
+            ]);
+            let ticks = futures::stream::unfold(0, |tick| async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let line = format!("2024-01-01T00:01:{:02}Z tick {}\n", tick % 60, tick);
+                Some((frame(&line), tick + 1))
+            });
+            boundary.chain(ticks)
+        };
+
+        let stream = tokio::time::timeout(
+            Duration::from_secs(5),
+            LogStream::start("test", history, follow),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(data(&stream).starts_with("one\ntwo\n"));
+        assert_eq!(*since.lock().unwrap(), Some(1704067202));
+
+        while !data(&stream).contains("tick 1\n") {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(data(&stream).starts_with("one\ntwo\nthree\ntick 0\ntick 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_history_error() {
+        let history =
+            futures::stream::iter([Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404,
+                message: String::from("No such container"),
+            })]);
+        let follow = |_| futures::stream::empty();
+
+        assert!(LogStream::start("test", history, follow).await.is_err());
+    }
+}
@@ -0,0 +1,776 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bollard::container::DownloadFromContainerOptions;
+use fuser::{FileAttr, FileType};
+use futures::{StreamExt, TryStreamExt};
+use tokio::{io::AsyncWriteExt, sync::OnceCell};
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+use crate::docker_strategy::docker::{errno_from_docker_error, errno_from_io_error};
+
+const TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct RootFsNode {
+    pub kind: FileType,
+    pub size: u64,
+    pub perm: u16,
+    pub mtime: u64,
+    pub link: Option<String>,
+    pub children: BTreeMap<String, FileType>,
+    // Position of the content in the archive the node was unpacked from.
+    offset: u64,
+    content: OnceCell<Arc<Vec<u8>>>,
+}
+
+impl RootFsNode {
+    fn directory() -> Self {
+        Self {
+            kind: FileType::Directory,
+            size: 0,
+            perm: 0o755,
+            mtime: 0,
+            link: None,
+            children: BTreeMap::new(),
+            offset: 0,
+            content: OnceCell::new(),
+        }
+    }
+
+    // File whose content isn't in any archive, as written through the mount.
+    fn file(perm: u16, content: Vec<u8>) -> Self {
+        Self {
+            kind: FileType::RegularFile,
+            size: content.len() as u64,
+            perm,
+            mtime: now(),
+            content: OnceCell::new_with(Some(Arc::new(content))),
+            ..Self::directory()
+        }
+    }
+
+    pub fn attr(&self, ino: u64) -> FileAttr {
+        let time = UNIX_EPOCH + Duration::from_secs(self.mtime);
+
+        FileAttr {
+            ino,
+            size: self.size,
+            blocks: 0,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: self.kind,
+            perm: self.perm,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
+
+// Read-only view of a container filesystem. Only the metadata is unpacked from the archive,
+// file contents are downloaded the first time they are opened and kept in their node.
+#[derive(Debug)]
+pub struct RootFs {
+    nodes: HashMap<String, RootFsNode>,
+    loaded_at: Instant,
+}
+
+impl RootFs {
+    pub async fn load(docker: &bollard::Docker, id: &str) -> Result<RootFs, libc::c_int> {
+        let archive = docker
+            .download_from_container(id, Some(DownloadFromContainerOptions { path: "/" }))
+            .map_err(std::io::Error::other);
+        let reader = SyncIoBridge::new(StreamReader::new(Box::pin(archive)));
+
+        let nodes = tokio::task::spawn_blocking(move || Self::unpack_metadata(reader, ""))
+            .await
+            .map_err(|_| libc::EIO)?
+            .map_err(|e| {
+                log::error!("Failed to unpack filesystem of container {}: {}", id, e);
+                libc::EIO
+            })?;
+
+        Ok(Self {
+            nodes,
+            loaded_at: Instant::now(),
+        })
+    }
+
+    // Keeps the archive of a path of a container on disk, the file only appearing once complete.
+    pub async fn download(
+        docker: &bollard::Docker,
+        id: &str,
+        path: &str,
+        archive: &Path,
+    ) -> Result<(), libc::c_int> {
+        if let Some(dir) = archive.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(errno_from_io_error)?;
+        }
+
+        let mut partial = archive.as_os_str().to_owned();
+        partial.push(".part");
+        let mut file = tokio::fs::File::create(&partial)
+            .await
+            .map_err(errno_from_io_error)?;
+
+        let mut chunks =
+            docker.download_from_container(id, Some(DownloadFromContainerOptions { path }));
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| {
+                log::error!("Failed to download {} from container {}: {}", path, id, e);
+                errno_from_docker_error(&e)
+            })?;
+            file.write_all(&chunk).await.map_err(errno_from_io_error)?;
+        }
+        file.flush().await.map_err(errno_from_io_error)?;
+
+        tokio::fs::rename(partial, archive)
+            .await
+            .map_err(errno_from_io_error)
+    }
+
+    // Same as `load`, from an archive kept on disk. Archives of a directory other than `/` hold
+    // it as their root entry, named `root`.
+    pub async fn load_archive(archive: PathBuf, root: &str) -> Result<RootFs, libc::c_int> {
+        let root = root.to_string();
+        let nodes = tokio::task::spawn_blocking(move || {
+            Self::unpack_metadata(BufReader::new(File::open(&archive)?), &root)
+        })
+        .await
+        .map_err(|_| libc::EIO)?
+        .map_err(|e| {
+            log::error!("Failed to unpack filesystem archive: {}", e);
+            e.raw_os_error().unwrap_or(libc::EIO)
+        })?;
+
+        Ok(Self {
+            nodes,
+            loaded_at: Instant::now(),
+        })
+    }
+
+    fn unpack_metadata(
+        reader: impl Read,
+        root: &str,
+    ) -> std::io::Result<HashMap<String, RootFsNode>> {
+        let mut nodes = HashMap::from([(String::new(), RootFsNode::directory())]);
+        let mut archive = tar::Archive::new(reader);
+
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let path = match Self::relative(&entry.path()?.to_string_lossy(), root) {
+                Some(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+
+            let header = entry.header();
+            let kind = match header.entry_type() {
+                tar::EntryType::Directory => FileType::Directory,
+                tar::EntryType::Symlink => FileType::Symlink,
+                tar::EntryType::Char => FileType::CharDevice,
+                tar::EntryType::Block => FileType::BlockDevice,
+                tar::EntryType::Fifo => FileType::NamedPipe,
+                _ => FileType::RegularFile,
+            };
+
+            // Directories missing from the archive are created along with their parents.
+            let (mut child, mut child_kind) = (path.as_str(), kind);
+            loop {
+                let (parent, name) = child.rsplit_once('/').unwrap_or(("", child));
+                let missing = !nodes.contains_key(parent);
+                nodes
+                    .entry(parent.to_string())
+                    .or_insert_with(RootFsNode::directory)
+                    .children
+                    .insert(name.to_string(), child_kind);
+
+                if !missing || parent.is_empty() {
+                    break;
+                }
+                (child, child_kind) = (parent, FileType::Directory);
+            }
+
+            // Hard links hold no content, they share the one of the entry they point to, named
+            // like paths of the archive.
+            let link = entry
+                .link_name()?
+                .map(|link| link.to_string_lossy().to_string());
+            let (link, size) = match (header.entry_type(), link) {
+                (tar::EntryType::Link, Some(link)) => {
+                    let target = Self::relative(&link, root);
+                    let size = target
+                        .as_ref()
+                        .and_then(|target| nodes.get(target))
+                        .map_or(0, |target| target.size);
+                    (target, size)
+                }
+                (_, link) => (link, entry.size()),
+            };
+
+            let node = RootFsNode {
+                kind,
+                size,
+                perm: header.mode().unwrap_or(0o644) as u16 & 0o7777,
+                mtime: header.mtime().unwrap_or_default(),
+                link,
+                children: BTreeMap::new(),
+                offset: entry.raw_file_position(),
+                content: OnceCell::new(),
+            };
+
+            // Directories may have been created earlier by one of their children.
+            match nodes.get_mut(&path) {
+                Some(existing) if kind == FileType::Directory => {
+                    existing.perm = node.perm;
+                    existing.mtime = node.mtime;
+                }
+                _ => {
+                    nodes.insert(path, node);
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    // The archive of a single file holds exactly one entry.
+    fn unpack_content(archive: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        if let Some(entry) = tar::Archive::new(archive).entries()?.next() {
+            entry?.read_to_end(&mut content)?;
+        }
+
+        Ok(content)
+    }
+
+    // Regular file holding the content of a path in the archive it was unpacked from, hard
+    // links pointing to the entry holding their content.
+    fn content_node(&self, path: &str) -> std::io::Result<&RootFsNode> {
+        let not_found = || std::io::Error::from_raw_os_error(libc::ENOENT);
+
+        let mut node = self.get(path).ok_or_else(not_found)?;
+        if let Some(link) = node
+            .link
+            .as_ref()
+            .filter(|_| node.kind == FileType::RegularFile)
+        {
+            node = self.get(&Self::normalize(link)).ok_or_else(not_found)?;
+        }
+        if node.kind != FileType::RegularFile {
+            return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+        }
+
+        Ok(node)
+    }
+
+    // Copies the content of a regular file out of the archive it was unpacked from.
+    pub fn extract(&self, archive: &Path, path: &str, target: &Path) -> std::io::Result<()> {
+        let node = self.content_node(path)?;
+
+        let mut archive = File::open(archive)?;
+        archive.seek(SeekFrom::Start(node.offset))?;
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Renamed once complete, a file being extracted is never opened.
+        let mut partial = target.as_os_str().to_owned();
+        partial.push(".part");
+        std::io::copy(&mut archive.take(node.size), &mut File::create(&partial)?)?;
+        std::fs::rename(partial, target)
+    }
+
+    // Reads the content of a regular file from the archive it was unpacked from, unless it was
+    // written since.
+    pub fn read(&self, archive: &File, path: &str) -> std::io::Result<Vec<u8>> {
+        let node = self.content_node(path)?;
+        if let Some(content) = node.content.get() {
+            return Ok(content.to_vec());
+        }
+
+        let mut content = vec![0; node.size as usize];
+        archive.read_exact_at(&mut content, node.offset)?;
+        Ok(content)
+    }
+
+    fn normalize(path: &str) -> String {
+        path.trim_start_matches("./").trim_matches('/').to_string()
+    }
+
+    // Path of an archive entry relative to the root of the tree, if it is inside of it.
+    fn relative(path: &str, root: &str) -> Option<String> {
+        let path = Self::normalize(path);
+        if root.is_empty() {
+            return Some(path);
+        }
+
+        match path.strip_prefix(root) {
+            Some(path) if path.is_empty() || path.starts_with('/') => {
+                Some(path.trim_matches('/').to_string())
+            }
+            _ => None,
+        }
+    }
+
+    // Directory holding a new entry, which must exist.
+    fn parent_of<'a>(&mut self, path: &'a str) -> Result<(&mut RootFsNode, &'a str), libc::c_int> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        match self.nodes.get_mut(parent) {
+            Some(node) if node.kind == FileType::Directory => Ok((node, name)),
+            Some(_) => Err(libc::ENOTDIR),
+            None => Err(libc::ENOENT),
+        }
+    }
+
+    fn insert(&mut self, path: &str, node: RootFsNode) -> Result<(), libc::c_int> {
+        let (parent, name) = self.parent_of(path)?;
+        parent.children.insert(name.to_string(), node.kind);
+        parent.mtime = now();
+
+        self.detach_links(&[path.to_string()]);
+        self.nodes.insert(path.to_string(), node);
+        Ok(())
+    }
+
+    // Paths of the entry at a path and of everything inside of it.
+    fn paths_under(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{}/", path);
+        self.nodes
+            .keys()
+            .filter(|node_path| *node_path == path || node_path.starts_with(&prefix))
+            .cloned()
+            .collect()
+    }
+
+    // Hard links to entries about to be removed or replaced take their content over, as they
+    // would keep the data of the inode they share on a filesystem.
+    fn detach_links(&mut self, paths: &[String]) {
+        let links: Vec<(String, String)> = self
+            .nodes
+            .iter()
+            .filter_map(|(path, node)| {
+                let link = node
+                    .link
+                    .as_ref()
+                    .filter(|_| node.kind == FileType::RegularFile)?;
+                let target = Self::normalize(link);
+                paths.contains(&target).then(|| (path.clone(), target))
+            })
+            .collect();
+
+        for (path, target) in links {
+            let Some((offset, size, content)) = self
+                .nodes
+                .get(&target)
+                .filter(|target| target.kind == FileType::RegularFile && target.link.is_none())
+                .map(|target| (target.offset, target.size, target.content.clone()))
+            else {
+                continue;
+            };
+
+            if let Some(node) = self.nodes.get_mut(&path) {
+                node.offset = offset;
+                node.size = size;
+                node.content = content;
+                node.link = None;
+            }
+        }
+    }
+
+    // Changes made through the mount are reflected in the tree, rather than downloading it
+    // again.
+    pub fn insert_directory(&mut self, path: &str) -> Result<(), libc::c_int> {
+        let directory = RootFsNode {
+            mtime: now(),
+            ..RootFsNode::directory()
+        };
+        self.insert(path, directory)
+    }
+
+    // Replaces the file at the path, if any.
+    pub fn insert_file(
+        &mut self,
+        path: &str,
+        perm: u16,
+        content: Vec<u8>,
+    ) -> Result<(), libc::c_int> {
+        self.insert(path, RootFsNode::file(perm, content))
+    }
+
+    // Drops the entry at the path and everything inside of it.
+    pub fn remove(&mut self, path: &str) -> Result<(), libc::c_int> {
+        let (parent, name) = self.parent_of(path)?;
+        parent.children.remove(name).ok_or(libc::ENOENT)?;
+        parent.mtime = now();
+
+        let removed = self.paths_under(path);
+        self.detach_links(&removed);
+        for path in removed {
+            self.nodes.remove(&path);
+        }
+        Ok(())
+    }
+
+    // Moves the entry at a path and everything inside of it, replacing the entry at the new path.
+    // Hard links to the moved entries follow them.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), libc::c_int> {
+        let kind = self.get(from).ok_or(libc::ENOENT)?.kind;
+        self.parent_of(to)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.get(to).is_some() {
+            self.remove(to)?;
+        }
+
+        let (parent, name) = self.parent_of(from)?;
+        parent.children.remove(name);
+        parent.mtime = now();
+        let (parent, name) = self.parent_of(to)?;
+        parent.children.insert(name.to_string(), kind);
+        parent.mtime = now();
+
+        let prefix = format!("{}/", from);
+        for path in self.paths_under(from) {
+            if let Some(node) = self.nodes.remove(&path) {
+                let new_path = format!("{}{}", to, &path[from.len()..]);
+                self.nodes.insert(new_path, node);
+            }
+        }
+
+        for node in self.nodes.values_mut() {
+            if node.kind != FileType::RegularFile {
+                continue;
+            }
+            if let Some(link) = node.link.as_mut() {
+                let target = Self::normalize(link);
+                if target == from || target.starts_with(&prefix) {
+                    *link = format!("{}{}", to, &target[from.len()..]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.loaded_at.elapsed() > TTL
+    }
+
+    pub fn get(&self, path: &str) -> Option<&RootFsNode> {
+        self.nodes.get(path)
+    }
+
+    pub async fn content(
+        &self,
+        docker: &bollard::Docker,
+        id: &str,
+        path: &str,
+    ) -> Result<Arc<Vec<u8>>, libc::c_int> {
+        let node = self.get(path).ok_or(libc::ENOENT)?;
+        if node.kind != FileType::RegularFile {
+            return Err(libc::EINVAL);
+        }
+
+        node.content
+            .get_or_try_init(|| async {
+                let archive = docker
+                    .download_from_container(
+                        id,
+                        Some(DownloadFromContainerOptions {
+                            path: format!("/{}", path),
+                        }),
+                    )
+                    .try_fold(Vec::new(), |mut archive, chunk| async move {
+                        archive.extend_from_slice(&chunk);
+                        Ok(archive)
+                    })
+                    .await
+                    .map_err(|e| {
+                        log::error!("Failed to download {} from container {}: {}", path, id, e);
+                        errno_from_docker_error(&e)
+                    })?;
+
+                Self::unpack_content(&archive).map(Arc::new).map_err(|e| {
+                    log::error!("Failed to unpack {} from container {}: {}", path, id, e);
+                    libc::EIO
+                })
+            })
+            .await
+            .cloned()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTNAME: &[u8] = b"hello world\n";
+
+    fn archive(prefix: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o700);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, format!("{}etc", prefix), std::io::empty())
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o640);
+        header.set_mtime(42);
+        header.set_size(HOSTNAME.len() as u64);
+        builder
+            .append_data(&mut header, format!("{}etc/hostname", prefix), HOSTNAME)
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        builder
+            .append_link(
+                &mut header,
+                format!("{}etc/hardlink", prefix),
+                format!("{}etc/hostname", prefix),
+            )
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, format!("{}usr/bin/sh", prefix), "busybox")
+            .unwrap();
+
+        builder.into_inner().unwrap()
+    }
+
+    fn tree(archive: &[u8], root: &str) -> RootFs {
+        RootFs {
+            nodes: RootFs::unpack_metadata(archive, root).unwrap(),
+            loaded_at: Instant::now(),
+        }
+    }
+
+    // Archive kept on disk, named after the test using it.
+    fn archive_file(name: &str, archive: &[u8]) -> File {
+        let path =
+            std::env::temp_dir().join(format!("fuse_docker_{}_{}.tar", name, std::process::id()));
+        std::fs::write(&path, archive).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_unpack_metadata_offsets() {
+        let archive = archive("");
+        let tree = tree(&archive, "");
+
+        let node = tree.get("etc/hostname").unwrap();
+        let offset = node.offset as usize;
+        assert_eq!(node.kind, FileType::RegularFile);
+        assert_eq!(node.perm, 0o640);
+        assert_eq!(node.mtime, 42);
+        assert_eq!(&archive[offset..offset + node.size as usize], HOSTNAME);
+    }
+
+    #[test]
+    fn test_unpack_metadata_directories() {
+        let tree = tree(&archive("./"), "");
+
+        let etc = tree.get("etc").unwrap();
+        assert_eq!(etc.perm, 0o700);
+        assert_eq!(
+            etc.children.keys().collect::<Vec<_>>(),
+            vec!["hardlink", "hostname"]
+        );
+        assert_eq!(
+            tree.get("").unwrap().children.keys().collect::<Vec<_>>(),
+            vec!["etc", "usr"]
+        );
+
+        // Directories missing from the archive are created by their children.
+        assert_eq!(tree.get("usr/bin").unwrap().kind, FileType::Directory);
+        assert_eq!(
+            tree.get("usr/bin/sh").unwrap().link.as_deref(),
+            Some("busybox")
+        );
+    }
+
+    #[test]
+    fn test_unpack_metadata_in_root() {
+        let tree = tree(&archive("data/"), "data");
+
+        assert!(tree.get("etc/hostname").is_some());
+        assert!(tree.get("data").is_none());
+        assert_eq!(
+            tree.get("etc/hardlink").unwrap().link.as_deref(),
+            Some("etc/hostname")
+        );
+    }
+
+    #[test]
+    fn test_hard_link_shares_content_of_its_target() {
+        let archive = archive("");
+        let tree = tree(&archive, "");
+
+        let link = tree.get("etc/hardlink").unwrap();
+        assert_eq!(link.kind, FileType::RegularFile);
+        assert_eq!(link.size, HOSTNAME.len() as u64);
+
+        let target = tree.content_node("etc/hardlink").unwrap();
+        assert_eq!(target.offset, tree.get("etc/hostname").unwrap().offset);
+
+        let file = archive_file("hard_link", &archive);
+        assert_eq!(tree.read(&file, "etc/hardlink").unwrap(), HOSTNAME);
+    }
+
+    #[test]
+    fn test_content_node_of_non_regular_file() {
+        let tree = tree(&archive(""), "");
+
+        let error = |path| tree.content_node(path).unwrap_err().raw_os_error();
+        assert_eq!(error("etc"), Some(libc::EINVAL));
+        assert_eq!(error("usr/bin/sh"), Some(libc::EINVAL));
+        assert_eq!(error("etc/missing"), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn test_insert_file_replaces_content() {
+        let archive = archive("");
+        let mut tree = tree(&archive, "");
+        let file = archive_file("insert_file", &archive);
+
+        tree.insert_file("etc/hostname", 0o600, b"other\n".to_vec())
+            .unwrap();
+        tree.insert_file("etc/new", 0o644, Vec::new()).unwrap();
+
+        assert_eq!(tree.read(&file, "etc/hostname").unwrap(), b"other\n");
+        assert_eq!(tree.get("etc/hostname").unwrap().size, 6);
+        assert!(tree.read(&file, "etc/new").unwrap().is_empty());
+        assert!(tree.get("etc").unwrap().children.contains_key("new"));
+        assert_eq!(
+            tree.insert_file("missing/file", 0o644, Vec::new()),
+            Err(libc::ENOENT)
+        );
+        assert_eq!(
+            tree.insert_file("etc/hostname/file", 0o644, Vec::new()),
+            Err(libc::ENOTDIR)
+        );
+    }
+
+    #[test]
+    fn test_rename_moves_everything_inside() {
+        let mut tree = tree(&archive(""), "");
+        tree.insert_directory("var").unwrap();
+
+        tree.rename("etc", "var/etc").unwrap();
+
+        assert!(tree.get("etc").is_none());
+        assert!(tree.get("etc/hostname").is_none());
+        assert!(tree.get("var/etc/hostname").is_some());
+        assert!(!tree.get("").unwrap().children.contains_key("etc"));
+        assert_eq!(
+            tree.get("var").unwrap().children.get("etc"),
+            Some(&FileType::Directory)
+        );
+    }
+
+    #[test]
+    fn test_rename_replaces_target() {
+        let archive = archive("");
+        let mut tree = tree(&archive, "");
+        let file = archive_file("rename_replaces", &archive);
+
+        tree.rename("etc/hardlink", "etc/hostname").unwrap();
+
+        assert!(tree.get("etc/hardlink").is_none());
+        assert!(tree.get("etc/hostname").unwrap().link.is_none());
+        assert_eq!(tree.read(&file, "etc/hostname").unwrap(), HOSTNAME);
+        assert_eq!(
+            tree.get("etc").unwrap().children.keys().collect::<Vec<_>>(),
+            vec!["hostname"]
+        );
+        assert_eq!(tree.rename("etc/missing", "etc/other"), Err(libc::ENOENT));
+    }
+
+    #[test]
+    fn test_hard_links_outlive_their_target() {
+        let archive = archive("");
+        let file = archive_file("links_outlive", &archive);
+
+        let mut removed = tree(&archive, "");
+        removed.remove("etc/hostname").unwrap();
+        assert_eq!(removed.read(&file, "etc/hardlink").unwrap(), HOSTNAME);
+
+        let mut replaced = tree(&archive, "");
+        replaced.rename("etc/hardlink", "etc/other").unwrap();
+        replaced.rename("usr/bin/sh", "etc/hostname").unwrap();
+        assert_eq!(replaced.read(&file, "etc/other").unwrap(), HOSTNAME);
+
+        let mut written = tree(&archive, "");
+        written
+            .insert_file("etc/hostname", 0o644, b"other\n".to_vec())
+            .unwrap();
+        assert_eq!(written.read(&file, "etc/hardlink").unwrap(), HOSTNAME);
+        assert_eq!(written.read(&file, "etc/hostname").unwrap(), b"other\n");
+    }
+
+    #[test]
+    fn test_hard_links_follow_their_target() {
+        let archive = archive("");
+        let file = archive_file("links_follow", &archive);
+        let mut tree = tree(&archive, "");
+        tree.insert_directory("var").unwrap();
+
+        tree.rename("etc/hostname", "etc/renamed").unwrap();
+        assert_eq!(
+            tree.get("etc/hardlink").unwrap().link.as_deref(),
+            Some("etc/renamed")
+        );
+
+        tree.rename("etc", "var/etc").unwrap();
+        assert_eq!(
+            tree.get("var/etc/hardlink").unwrap().link.as_deref(),
+            Some("var/etc/renamed")
+        );
+        assert_eq!(tree.read(&file, "var/etc/hardlink").unwrap(), HOSTNAME);
+    }
+
+    #[test]
+    fn test_remove_drops_everything_inside() {
+        let mut tree = tree(&archive(""), "");
+
+        tree.remove("usr").unwrap();
+
+        assert!(tree.get("usr").is_none());
+        assert!(tree.get("usr/bin/sh").is_none());
+        assert!(!tree.get("").unwrap().children.contains_key("usr"));
+        assert_eq!(tree.remove("usr"), Err(libc::ENOENT));
+    }
+}
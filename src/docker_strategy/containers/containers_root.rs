@@ -1,19 +1,52 @@
+use bollard::container::RemoveContainerOptions;
 use fuser::{FileAttr, FileType};
-use std::{
-    sync::Arc,
-    time::{Duration, UNIX_EPOCH},
-};
+use std::{sync::Arc, time::UNIX_EPOCH};
 use tokio::sync::Mutex;
 
-use crate::docker_strategy::{
-    child_directories::child_directories::ChildDirectory, containers::Container,
-    parent_directories::ParentDirectories,
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        containers::{Container, DraftContainer},
+        docker::errno_from_docker_error,
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
 };
 
 impl ParentDirectories {
     pub(crate) async fn containers_root_lookup(
         name: &std::ffi::OsStr,
-        reply: fuser::ReplyEntry,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_containers().await {
+            log::error!("Failed to update containers: {}", e);
+        }
+
+        let container_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let containers: Vec<&dyn ChildDirectory> = docker
+            .get::<Container>()
+            .into_iter()
+            .filter(|container| container.get_name() == container_name)
+            .map(|container| container as &dyn ChildDirectory)
+            .chain(
+                docker
+                    .get::<DraftContainer>()
+                    .into_iter()
+                    .filter(|draft| draft.get_name() == container_name)
+                    .map(|draft| draft as &dyn ChildDirectory),
+            )
+            .collect();
+
+        let attr = containers.first().ok_or(libc::ENOENT)?.getattr("")?;
+        docker.remember(attr.ino);
+
+        Ok(attr)
+    }
+
+    pub(crate) async fn containers_root_rmdir(
+        name: &std::ffi::OsStr,
         docker: Arc<Mutex<crate::docker_strategy::Docker>>,
     ) -> Result<(), libc::c_int> {
         let mut docker = docker.lock().await;
@@ -21,26 +54,75 @@ impl ParentDirectories {
             log::error!("Failed to update containers: {}", e);
         }
 
-        let container_name = if let Some(name) = name.to_str() {
-            name
-        } else {
-            reply.error(libc::ENOENT);
+        let container_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        // Drafts only exist in the mount until their container is created.
+        if let Some(ino) = docker
+            .get::<DraftContainer>()
+            .into_iter()
+            .find(|draft| draft.get_name() == container_name && !draft.is_created())
+            .map(|draft| draft.get_ino())
+        {
+            docker.remove_child(ino);
             return Ok(());
-        };
+        }
 
-        let containers: Vec<&Container> = docker
+        let (ino, id) = docker
             .get::<Container>()
             .into_iter()
-            .filter(|container| container.get_name().starts_with(container_name))
-            .collect();
+            .find(|container| container.get_name() == container_name)
+            .map(|container| (container.get_ino(), container.get_id().clone()))
+            .ok_or(libc::ENOENT)?;
+
+        let options = docker.get_options();
+        docker
+            .get_docker()
+            .remove_container(
+                &id,
+                Some(RemoveContainerOptions {
+                    force: options.rmdir_force,
+                    v: options.rmdir_volumes,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to remove container {}: {}", container_name, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        docker.remove_child(ino);
+        Ok(())
+    }
+
+    pub(crate) async fn containers_root_mkdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_containers().await {
+            log::error!("Failed to update containers: {}", e);
+        }
 
-        if let Some(container) = containers.first() {
-            reply.entry(&Duration::from_secs(1), &container.getattr(), 0);
-            Ok(())
-        } else {
-            reply.error(libc::ENOENT);
-            Ok(())
+        let container_name = name.to_str().ok_or(libc::EINVAL)?;
+
+        let exists = docker
+            .get::<Container>()
+            .into_iter()
+            .any(|container| container.get_name() == container_name)
+            || docker
+                .get::<DraftContainer>()
+                .into_iter()
+                .any(|draft| draft.get_name() == container_name);
+        if exists {
+            return Err(libc::EEXIST);
         }
+
+        let draft = DraftContainer::new(container_name, docker.get_inodes());
+        let attr = draft.dir_attr();
+        docker.insert_child(draft.into());
+
+        Ok(attr)
     }
 
     pub(crate) fn containers_root_attr(&self) -> FileAttr {
@@ -65,15 +147,15 @@ impl ParentDirectories {
 
     pub(crate) async fn containers_root_read_dir(
         &self,
-        offset: i64,
-        reply: &mut fuser::ReplyDirectory,
         docker: Arc<Mutex<crate::docker_strategy::Docker>>,
-    ) -> Result<(), libc::c_int> {
-        log::debug!("containers_read_dir(offset: {})", offset);
-
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
         let mut entries = vec![
-            (ParentDirectories::Containers as u64, String::from(".")),
-            (ParentDirectories::Root as u64, String::from("..")),
+            DirectoryEntry::new(
+                ParentDirectories::Containers as u64,
+                FileType::Directory,
+                ".",
+            ),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
         ];
 
         let mut docker = docker.lock().await;
@@ -87,22 +169,25 @@ impl ParentDirectories {
         log::debug!("containers_read_dir: containers: {:?}", containers);
 
         containers.into_iter().for_each(|container| {
-            if let (Some(id), Some(names)) =
-                (container.container.id.as_ref(), &container.container.names)
-            {
-                let name_string = names.first().unwrap().to_owned();
-
-                entries.push((Self::ino_from_docker_id(id), name_string))
-            }
+            entries.push(DirectoryEntry::new(
+                container.get_ino(),
+                FileType::Directory,
+                container.get_name(),
+            ))
         });
 
-        for (i, (ino, name)) in entries.iter().enumerate().skip(offset as usize) {
-            log::debug!("containers_read_dir: {} {} {}", i, ino, name);
-            if reply.add(*ino, i as i64 + 1, FileType::Directory, name) {
-                break;
-            }
-        }
-
-        Ok(())
+        docker
+            .get::<DraftContainer>()
+            .into_iter()
+            .filter(|draft| !draft.is_created())
+            .for_each(|draft| {
+                entries.push(DirectoryEntry::new(
+                    draft.get_ino(),
+                    FileType::Directory,
+                    draft.get_name(),
+                ))
+            });
+
+        Ok(entries)
     }
 }
@@ -0,0 +1,334 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use bollard::container::{Config, CreateContainerOptions};
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        containers::ContainerCommand,
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const SPEC: &str = "spec";
+const CTL: &str = "ctl";
+
+// State of a draft shared with its open files, the control file creating the container.
+#[derive(Debug)]
+pub struct DraftState {
+    name: String,
+    spec: Arc<Mutex<Vec<u8>>>,
+    id: Mutex<Option<String>>,
+}
+
+impl DraftState {
+    pub fn is_created(&self) -> bool {
+        self.id.lock().unwrap().is_some()
+    }
+
+    // Accepts the same commands as the control file of a container, plus `create`. `start`
+    // creates the container first when needed.
+    pub async fn run_all(&self, docker: &bollard::Docker, data: &[u8]) -> Result<(), libc::c_int> {
+        let commands = std::str::from_utf8(data).map_err(|_| libc::EINVAL)?;
+
+        for command in commands
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let id = self.id.lock().unwrap().clone();
+            let id = match id {
+                Some(id) => id,
+                None if command == "create" || command == "start" => self.create(docker).await?,
+                None => return Err(libc::EINVAL),
+            };
+
+            if command != "create" {
+                ContainerCommand::try_from(command)
+                    .map_err(|_| libc::EINVAL)?
+                    .run(docker, &id)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create(&self, docker: &bollard::Docker) -> Result<String, libc::c_int> {
+        let config = self.parse_spec()?;
+
+        let response = docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: self.name.as_str(),
+                    ..Default::default()
+                }),
+                config,
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to create container {}: {}", self.name, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        *self.id.lock().unwrap() = Some(response.id.clone());
+        Ok(response.id)
+    }
+
+    // The spec is the body of the create API, JSON being tried first since YAML is a superset.
+    fn parse_spec(&self) -> Result<Config<String>, libc::c_int> {
+        let spec = self.spec.lock().unwrap();
+
+        serde_json::from_slice(&spec)
+            .or_else(|_| serde_yaml::from_slice(&spec))
+            .map_err(|e| {
+                log::error!("Invalid spec for container {}: {}", self.name, e);
+                libc::EINVAL
+            })
+    }
+}
+
+// Placeholder created by `mkdir` in the containers directory, until a container is created from
+// the spec written in it.
+#[derive(Debug)]
+pub struct DraftContainer {
+    ino: u64,
+    name: String,
+    created: SystemTime,
+    state: Arc<DraftState>,
+    inodes: Inodes,
+}
+
+impl From<DraftContainer> for Box<dyn ChildDirectory> {
+    fn from(value: DraftContainer) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for DraftContainer {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Containers
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if !path.is_empty() {
+                return Err(libc::ENOTDIR);
+            }
+
+            Ok(vec![
+                DirectoryEntry::new(self.ino, FileType::Directory, "."),
+                DirectoryEntry::new(
+                    ParentDirectories::Containers as u64,
+                    FileType::Directory,
+                    "..",
+                ),
+                DirectoryEntry::new(self.entry_ino(SPEC), FileType::RegularFile, SPEC),
+                DirectoryEntry::new(self.entry_ino(CTL), FileType::RegularFile, CTL),
+            ])
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move { self.getattr(&join_path(path, name)) }.boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match path {
+            "" => Ok(self.dir_attr()),
+            SPEC => Ok(FileAttr {
+                ino: self.entry_ino(SPEC),
+                size: self.state.spec.lock().unwrap().len() as u64,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                ..self.dir_attr()
+            }),
+            CTL => Ok(FileAttr {
+                ino: self.entry_ino(CTL),
+                kind: FileType::RegularFile,
+                perm: 0o200,
+                nlink: 1,
+                ..self.dir_attr()
+            }),
+            _ => Err(libc::ENOENT),
+        }
+    }
+
+    fn set_size(&self, path: &str, size: u64) -> Result<FileAttr, libc::c_int> {
+        match path {
+            SPEC => self.state.spec.lock().unwrap().resize(size as usize, 0),
+            CTL => (),
+            _ => return Err(libc::EACCES),
+        }
+
+        self.getattr(path)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            match path {
+                SPEC => Ok(FileHandle::Buffer(self.state.spec.clone())),
+                CTL => Ok(FileHandle::DraftControl {
+                    docker: docker.clone(),
+                    draft: self.state.clone(),
+                }),
+                _ => Err(libc::ENOENT),
+            }
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, _path: &str) -> Result<String, libc::c_int> {
+        Err(libc::EINVAL)
+    }
+}
+
+impl DraftContainer {
+    pub fn new(name: &str, inodes: &Inodes) -> Self {
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::DraftContainer, name),
+            name: name.to_string(),
+            created: SystemTime::now(),
+            state: Arc::new(DraftState {
+                name: name.to_string(),
+                spec: Arc::new(Mutex::new(Vec::new())),
+                id: Mutex::new(None),
+            }),
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn is_created(&self) -> bool {
+        self.state.is_created()
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::DraftContainer, &self.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(spec: &str) -> DraftState {
+        DraftState {
+            name: String::from("web"),
+            spec: Arc::new(Mutex::new(spec.as_bytes().to_vec())),
+            id: Mutex::new(None),
+        }
+    }
+
+    #[test]
+    fn test_parse_json_spec() {
+        let config = state(r#"{"Image": "nginx", "Cmd": ["nginx", "-g", "daemon off;"]}"#)
+            .parse_spec()
+            .unwrap();
+
+        assert_eq!(config.image.as_deref(), Some("nginx"));
+        assert_eq!(
+            config.cmd,
+            Some(vec![
+                String::from("nginx"),
+                String::from("-g"),
+                String::from("daemon off;"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml_spec() {
+        let config = state("Image: alpine\nEnv:\n  - A=1\nTty: true\n")
+            .parse_spec()
+            .unwrap();
+
+        assert_eq!(config.image.as_deref(), Some("alpine"));
+        assert_eq!(config.env, Some(vec![String::from("A=1")]));
+        assert_eq!(config.tty, Some(true));
+    }
+
+    #[test]
+    fn test_parse_invalid_spec() {
+        assert_eq!(state("Image: [").parse_spec().err(), Some(libc::EINVAL));
+        assert_eq!(state("Tty: maybe").parse_spec().err(), Some(libc::EINVAL));
+    }
+
+    #[tokio::test]
+    async fn test_commands_need_a_container() {
+        let docker = bollard::Docker::connect_with_http(
+            "http://127.0.0.1:1",
+            1,
+            bollard::API_DEFAULT_VERSION,
+        )
+        .unwrap();
+        let state = state("");
+
+        assert_eq!(state.run_all(&docker, b"stop\n").await, Err(libc::EINVAL));
+        assert_eq!(state.run_all(&docker, &[0xff]).await, Err(libc::EINVAL));
+        assert_eq!(state.run_all(&docker, b"\n  \n").await, Ok(()));
+        assert!(!state.is_created());
+    }
+}
@@ -1,4 +1,14 @@
 pub(crate) mod container;
+pub(crate) mod container_ctl;
+pub(crate) mod container_file;
+pub(crate) mod container_logs;
+pub(crate) mod container_rootfs;
 pub(crate) mod containers_root;
+pub(crate) mod draft_container;
 
 pub(crate) use container::Container;
+pub(crate) use container_ctl::ContainerCommand;
+pub(crate) use container_file::ContainerFile;
+pub(crate) use container_logs::{LogStream, LogWriter};
+pub(crate) use container_rootfs::RootFs;
+pub(crate) use draft_container::{DraftContainer, DraftState};
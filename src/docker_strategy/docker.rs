@@ -1,34 +1,111 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use bollard::container::ListContainersOptions;
+use bollard::{
+    container::ListContainersOptions,
+    image::ListImagesOptions,
+    network::{InspectNetworkOptions, ListNetworksOptions},
+    service::ContainerSummary,
+    volume::ListVolumesOptions,
+};
 use tokio::{runtime::Handle, time::Instant};
 
-use crate::docker_strategy::containers::Container;
+use crate::{
+    docker_strategy::{
+        containers::{Container, DraftContainer},
+        images::{
+            image::{escape_name, unescape_name},
+            Image, ImagesById, PullState, PullingImage, UntaggedImages,
+        },
+        networks::{DraftNetwork, Network},
+        parent_directories::ParentDirectories,
+        pods::{libpod, Pod},
+        volumes::{DraftVolume, Volume},
+    },
+    fuse_handler::Invalidation,
+};
 
-use super::child_directories::child_directories::ChildDirectory;
+use super::{
+    child_directories::child_directories::ChildDirectory,
+    inode_table::{InodeTable, Inodes},
+    DockerOptions,
+};
 
 const TTL: Duration = Duration::from_secs(5);
 
+pub(crate) fn errno_from_docker_error(error: &bollard::errors::Error) -> libc::c_int {
+    match error {
+        bollard::errors::Error::DockerResponseServerError { status_code, .. } => {
+            match status_code {
+                304 => libc::EALREADY,
+                404 => libc::ENOENT,
+                409 => libc::EBUSY,
+                _ => libc::EIO,
+            }
+        }
+        _ => libc::EIO,
+    }
+}
+
+pub(crate) fn errno_from_io_error(error: std::io::Error) -> libc::c_int {
+    error.raw_os_error().unwrap_or(libc::EIO)
+}
+
+// Objects destroyed before an event about them is handled are missing rather than failing.
+fn found<T>(
+    result: Result<T, bollard::errors::Error>,
+) -> Result<Option<T>, bollard::errors::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Containers without a name nor an id are left out of the mount. Docker names start with a `/`,
+// podman names don't.
+fn is_listed(container: &ContainerSummary) -> bool {
+    if let (Some(names), Some(_)) = (&container.names, &container.id) {
+        names
+            .iter()
+            .any(|name| !name.trim_start_matches('/').is_empty())
+    } else {
+        false
+    }
+}
+
 pub struct Docker {
     docker: bollard::Docker,
-    mappings: HashMap<u64, Box<dyn ChildDirectory>>,
+    mappings: HashMap<u64, Arc<dyn ChildDirectory>>,
+    inodes: Inodes,
+    options: DockerOptions,
     clock_since_last_update: Instant,
+    clock_since_last_images_update: Option<Instant>,
+    clock_since_last_volumes_update: Option<Instant>,
+    clock_since_last_networks_update: Option<Instant>,
+    clock_since_last_pods_update: Option<Instant>,
+    following_events: bool,
 }
 
 impl Docker {
-    pub fn new() -> Self {
-        let docker = match bollard::Docker::connect_with_local_defaults() {
-            Ok(docker) => docker,
-            Err(e) => {
-                log::error!("Failed to connect to docker daemon: {}", e);
-                panic!("Failed to connect to docker daemon: {}", e)
-            }
-        };
-
+    pub fn new(docker: bollard::Docker, options: DockerOptions) -> Self {
         let mut docker = Self {
             docker,
             clock_since_last_update: Instant::now(),
+            clock_since_last_images_update: None,
+            clock_since_last_volumes_update: None,
+            clock_since_last_networks_update: None,
+            clock_since_last_pods_update: None,
+            following_events: false,
             mappings: HashMap::new(),
+            inodes: Arc::new(Mutex::new(InodeTable::new())),
+            options,
         };
 
         Handle::current().block_on(async {
@@ -40,51 +117,551 @@ impl Docker {
         docker
     }
 
-    pub fn get_child(&self, inode: u64) -> Option<&Box<dyn ChildDirectory>> {
-        self.mappings.get(&inode)
+    /// Resolves an inode to the child directory owning it and the path of the inode in it.
+    pub fn resolve(&self, inode: u64) -> Option<(Arc<dyn ChildDirectory>, String)> {
+        let (object, path) = {
+            let inodes = self.inodes.lock().unwrap();
+            let key = inodes.get(inode)?;
+            (inodes.find(&key.object())?, key.path.clone())
+        };
 
-        // .and_then(|child| {
-        //     if let Some(child) = child.downcast_ref::<Box<dyn ChildDirectory>>() {
-        //         log::debug!("Found child: {:?}", child);
-        //         Some(child)
-        //     } else {
-        //         log::debug!("No child: {:?}, {:?}", child, self.mappings);
-        //         None
-        //     }
-        // })
+        self.mappings
+            .get(&object)
+            .map(|child| (child.clone(), path))
+    }
+
+    pub fn get_inodes(&self) -> &Inodes {
+        &self.inodes
+    }
+
+    pub fn remember(&self, inode: u64) {
+        self.inodes.lock().unwrap().remember(inode);
+    }
+
+    pub fn forget(&self, inode: u64, lookups: u64) {
+        self.inodes.lock().unwrap().forget(inode, lookups);
+    }
+
+    pub fn insert_child(&mut self, child: Box<dyn ChildDirectory>) {
+        self.mappings.insert(child.get_ino(), child.into());
+    }
+
+    // Forgets a child directory, inodes inside of it being released with it.
+    pub fn remove_child(&mut self, inode: u64) {
+        self.mappings.remove(&inode);
+        self.inodes.lock().unwrap().unpin(inode);
+    }
+
+    pub fn get_options(&self) -> &DockerOptions {
+        &self.options
+    }
+
+    // Listings are only refreshed once their TTL expired, unless events of the daemon keep the
+    // mappings up to date. Containers always expire, their status and health changing with time
+    // without any event.
+    fn is_fresh(&self, clock: Instant) -> bool {
+        self.following_events || clock.elapsed() < TTL
+    }
+
+    // Everything is listed again once subscribed to the events, those missed while the stream
+    // was down being lost.
+    pub async fn start_following_events(&mut self) -> Result<(), bollard::errors::Error> {
+        self.force_update_containers().await?;
+        self.force_update_images().await?;
+        self.force_update_volumes().await?;
+        self.force_update_networks().await?;
+
+        self.following_events = true;
+        Ok(())
+    }
+
+    pub fn stop_following_events(&mut self) {
+        self.following_events = false;
     }
 
     async fn force_update_containers(&mut self) -> Result<(), bollard::errors::Error> {
-        self.docker
+        let containers: Vec<Container> = self
+            .docker
             .list_containers(Some(ListContainersOptions::<String> {
                 all: true,
                 ..Default::default()
             }))
             .await?
             .into_iter()
-            .filter(|container| {
-                if let (Some(names), Some(_)) = (&container.names, &container.id) {
-                    names.iter().any(|name| name.starts_with("/"))
-                } else {
-                    false
-                }
+            .filter(is_listed)
+            .map(|container_summary| Container::new(container_summary, &self.inodes))
+            .collect();
+
+        let removed: Vec<u64> = self
+            .get::<Container>()
+            .into_iter()
+            .map(|container| container.get_ino())
+            .filter(|inode| {
+                !containers
+                    .iter()
+                    .any(|container| container.get_ino() == *inode)
             })
-            .for_each(|container_summary| {
-                let container = Container::new(container_summary);
-                let inode = container.get_ino();
-                self.mappings.insert(inode, Box::new(container));
-            });
+            .collect();
+        removed
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+
+        containers.into_iter().for_each(|mut container| {
+            let inode = container.get_ino();
+            if let Some(previous) = self
+                .mappings
+                .get(&inode)
+                .and_then(|child| child.as_container())
+            {
+                container.keep_state(previous);
+            }
+            self.mappings.insert(inode, Arc::new(container));
+        });
 
         self.clock_since_last_update = Instant::now();
         Ok(())
     }
 
+    // Drafts are replaced by their container as soon as it has been created.
     pub async fn update_containers(&mut self) -> Result<(), bollard::errors::Error> {
-        if self.clock_since_last_update.elapsed() < TTL {
+        let created_drafts: Vec<u64> = self
+            .get::<DraftContainer>()
+            .into_iter()
+            .filter(|draft| draft.is_created())
+            .map(|draft| draft.get_ino())
+            .collect();
+
+        if self.clock_since_last_update.elapsed() < TTL && created_drafts.is_empty() {
             return Ok(());
         }
 
-        self.force_update_containers().await
+        self.force_update_containers().await?;
+        created_drafts
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+        Ok(())
+    }
+
+    // Images are rebuilt from scratch, one child directory per image id. Pulls that succeeded
+    // are replaced by the tag they pulled.
+    pub async fn force_update_images(&mut self) -> Result<(), bollard::errors::Error> {
+        let pulled: Vec<(u64, String, Arc<PullState>)> = self
+            .get::<PullingImage>()
+            .into_iter()
+            .filter(|pull| pull.is_pulled())
+            .map(|pull| {
+                (
+                    pull.get_ino(),
+                    pull.get_name().clone(),
+                    pull.get_state().clone(),
+                )
+            })
+            .collect();
+
+        let mut by_id = ImagesById::new(&self.inodes);
+        let mut untagged = UntaggedImages::new(&self.inodes);
+        let images: Vec<Image> = self
+            .docker
+            .list_images(Some(ListImagesOptions::<String>::default()))
+            .await?
+            .into_iter()
+            .map(|summary| {
+                let image = Image::new(
+                    summary,
+                    by_id.get_ino(),
+                    &self.inodes,
+                    &self.options.cache_dir,
+                );
+                by_id.add(&image);
+                untagged.add(&image);
+                image
+            })
+            .collect();
+
+        let removed: Vec<u64> = self
+            .get::<Image>()
+            .into_iter()
+            .filter(|removed| {
+                !images
+                    .iter()
+                    .any(|image| image.get_ino() == removed.get_ino())
+            })
+            .map(|removed| {
+                removed.remove_cache();
+                removed.get_ino()
+            })
+            .collect();
+        removed
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+
+        images.into_iter().for_each(|mut image| {
+            if let Some(previous) = self
+                .mappings
+                .get(&image.get_ino())
+                .and_then(|child| child.as_image())
+            {
+                image.keep_state(previous);
+            }
+            if let Some((_, _, state)) = pulled
+                .iter()
+                .find(|(_, name, _)| image.has_tag(&unescape_name(name)))
+            {
+                image.set_pull(state.clone());
+            }
+            self.insert_child(image.into());
+        });
+        self.insert_child(by_id.into());
+        self.insert_child(untagged.into());
+        pulled
+            .into_iter()
+            .for_each(|(inode, _, _)| self.remove_child(inode));
+
+        self.clock_since_last_images_update = Some(Instant::now());
+        Ok(())
+    }
+
+    pub async fn update_images(&mut self) -> Result<(), bollard::errors::Error> {
+        let pulled = self
+            .get::<PullingImage>()
+            .into_iter()
+            .any(|pull| pull.is_pulled());
+
+        match self.clock_since_last_images_update {
+            Some(clock) if self.is_fresh(clock) && !pulled => Ok(()),
+            _ => self.force_update_images().await,
+        }
+    }
+
+    async fn force_update_volumes(&mut self) -> Result<(), bollard::errors::Error> {
+        let volumes: Vec<Volume> = self
+            .docker
+            .list_volumes(None::<ListVolumesOptions<String>>)
+            .await?
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|volume| Volume::new(volume, &self.docker, &self.inodes, &self.options))
+            .collect();
+
+        let removed: Vec<u64> = self
+            .get::<Volume>()
+            .into_iter()
+            .map(|volume| volume.get_ino())
+            .filter(|inode| !volumes.iter().any(|volume| volume.get_ino() == *inode))
+            .collect();
+        removed
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+
+        volumes.into_iter().for_each(|mut volume| {
+            if let Some(previous) = self
+                .mappings
+                .get(&volume.get_ino())
+                .and_then(|child| child.as_volume())
+            {
+                volume.keep_state(previous);
+            }
+            self.insert_child(volume.into());
+        });
+
+        self.clock_since_last_volumes_update = Some(Instant::now());
+        Ok(())
+    }
+
+    // Drafts are replaced by their volume as soon as it has been created.
+    pub async fn update_volumes(&mut self) -> Result<(), bollard::errors::Error> {
+        let created = self
+            .get::<DraftVolume>()
+            .into_iter()
+            .any(|draft| draft.is_created());
+
+        match self.clock_since_last_volumes_update {
+            Some(clock) if self.is_fresh(clock) && !created => Ok(()),
+            _ => self.force_update_volumes().await,
+        }
+    }
+
+    async fn force_update_networks(&mut self) -> Result<(), bollard::errors::Error> {
+        let networks: Vec<Network> = self
+            .docker
+            .list_networks(None::<ListNetworksOptions<String>>)
+            .await?
+            .into_iter()
+            .map(|network| Network::new(network, &self.docker, &self.inodes))
+            .collect();
+
+        let removed: Vec<u64> = self
+            .get::<Network>()
+            .into_iter()
+            .map(|network| network.get_ino())
+            .filter(|inode| !networks.iter().any(|network| network.get_ino() == *inode))
+            .collect();
+        removed
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+
+        networks.into_iter().for_each(|mut network| {
+            if let Some(previous) = self
+                .mappings
+                .get(&network.get_ino())
+                .and_then(|child| child.as_network())
+            {
+                network.keep_state(previous);
+            }
+            self.insert_child(network.into());
+        });
+
+        self.clock_since_last_networks_update = Some(Instant::now());
+        Ok(())
+    }
+
+    // Drafts are replaced by their network as soon as it has been created.
+    pub async fn update_networks(&mut self) -> Result<(), bollard::errors::Error> {
+        let created = self
+            .get::<DraftNetwork>()
+            .into_iter()
+            .any(|draft| draft.is_created());
+
+        match self.clock_since_last_networks_update {
+            Some(clock) if self.is_fresh(clock) && !created => Ok(()),
+            _ => self.force_update_networks().await,
+        }
+    }
+
+    // Pods only exist when mounting podman.
+    async fn force_update_pods(&mut self) -> std::io::Result<()> {
+        let address = match &self.options.podman {
+            Some(address) => address,
+            None => return Ok(()),
+        };
+
+        let pods: Vec<Pod> = libpod::list_pods(address)
+            .await?
+            .into_iter()
+            .filter_map(|pod| Pod::new(pod, &self.inodes))
+            .collect();
+
+        let removed: Vec<u64> = self
+            .get::<Pod>()
+            .into_iter()
+            .map(|pod| pod.get_ino())
+            .filter(|inode| !pods.iter().any(|pod| pod.get_ino() == *inode))
+            .collect();
+        removed
+            .into_iter()
+            .for_each(|inode| self.remove_child(inode));
+
+        pods.into_iter()
+            .for_each(|pod| self.insert_child(pod.into()));
+
+        self.clock_since_last_pods_update = Some(Instant::now());
+        Ok(())
+    }
+
+    // The events stream has no pods, they are always refreshed once their TTL expired.
+    pub async fn update_pods(&mut self) -> std::io::Result<()> {
+        match self.clock_since_last_pods_update {
+            Some(clock) if clock.elapsed() < TTL => Ok(()),
+            _ => self.force_update_pods().await,
+        }
+    }
+
+    // Replaces the child directory of an object after an event of the daemon about it, given its
+    // previous inode and name, and returns the kernel caches it made stale. Entries are only
+    // dropped for renamed or removed objects, dropping a directory in use would detach it from
+    // the processes inside of it.
+    fn replace_child(
+        &mut self,
+        parent: ParentDirectories,
+        previous: Option<(u64, String)>,
+        child: Option<Box<dyn ChildDirectory>>,
+    ) -> Vec<Invalidation> {
+        let mut invalidations = Vec::new();
+        let inode = child.as_ref().map(|child| child.get_ino());
+
+        if let Some((previous_inode, name)) = previous {
+            invalidations.push(Invalidation::Inode {
+                ino: previous_inode,
+            });
+            if child.as_ref().map(|child| child.get_name()) != Some(&name) {
+                invalidations.push(Invalidation::Entry {
+                    parent: parent as u64,
+                    name: OsString::from(name),
+                });
+            }
+            if inode != Some(previous_inode) {
+                self.remove_child(previous_inode);
+            }
+        }
+
+        if let Some(child) = child {
+            self.insert_child(child);
+        }
+
+        invalidations
+    }
+
+    pub async fn refresh_container(
+        &mut self,
+        id: &str,
+    ) -> Result<Vec<Invalidation>, bollard::errors::Error> {
+        let summary = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters: HashMap::from([("id", vec![id])]),
+                ..Default::default()
+            }))
+            .await?
+            .into_iter()
+            .find(|container| container.id.as_deref() == Some(id) && is_listed(container));
+
+        let previous = self
+            .get::<Container>()
+            .into_iter()
+            .find(|container| container.get_id() == id)
+            .map(|container| (container.get_ino(), container.get_name().clone()));
+
+        let container = summary.map(|summary| {
+            let mut container = Container::new(summary, &self.inodes);
+            if let Some(previous) = self
+                .mappings
+                .get(&container.get_ino())
+                .and_then(|child| child.as_container())
+            {
+                container.keep_state(previous);
+            }
+            Box::new(container) as Box<dyn ChildDirectory>
+        });
+
+        Ok(self.replace_child(ParentDirectories::Containers, previous, container))
+    }
+
+    // Tags move between images, images are listed again as a whole.
+    pub async fn refresh_images(&mut self) -> Result<Vec<Invalidation>, bollard::errors::Error> {
+        let before = self.image_entries();
+        self.force_update_images().await?;
+        let after = self.image_entries();
+
+        Ok(before
+            .symmetric_difference(&after)
+            .map(|(parent, name)| Invalidation::Entry {
+                parent: *parent,
+                name: OsString::from(name),
+            })
+            .collect())
+    }
+
+    // Tags in the images directory, image directories in the `by-id` group and links in the
+    // `untagged` group.
+    fn image_entries(&self) -> HashSet<(u64, String)> {
+        let by_id = self
+            .get::<ImagesById>()
+            .first()
+            .map(|by_id| by_id.get_ino());
+        let untagged = self
+            .get::<UntaggedImages>()
+            .first()
+            .map(|untagged| untagged.get_ino());
+
+        self.get::<Image>()
+            .into_iter()
+            .flat_map(|image| {
+                image
+                    .get_tags()
+                    .iter()
+                    .map(|tag| (ParentDirectories::Images as u64, escape_name(tag)))
+                    .chain(by_id.map(|by_id| (by_id, image.get_name().clone())))
+                    .chain(
+                        untagged
+                            .filter(|_| image.get_tags().is_empty())
+                            .map(|untagged| (untagged, image.short_id().to_string())),
+                    )
+            })
+            .collect()
+    }
+
+    // Volumes are named by their id. Drafts are replaced by their volume, like on a refresh.
+    pub async fn refresh_volume(
+        &mut self,
+        name: &str,
+    ) -> Result<Vec<Invalidation>, bollard::errors::Error> {
+        let volume = found(self.docker.inspect_volume(name).await)?;
+
+        let previous = self
+            .get::<Volume>()
+            .into_iter()
+            .find(|volume| volume.get_name() == name)
+            .map(|volume| (volume.get_ino(), volume.get_name().clone()));
+
+        let volume = volume.map(|volume| {
+            let mut volume = Volume::new(volume, &self.docker, &self.inodes, &self.options);
+            if let Some(previous) = self
+                .mappings
+                .get(&volume.get_ino())
+                .and_then(|child| child.as_volume())
+            {
+                volume.keep_state(previous);
+            }
+            Box::new(volume) as Box<dyn ChildDirectory>
+        });
+
+        Ok(self.replace_child(ParentDirectories::Volumes, previous, volume))
+    }
+
+    pub async fn refresh_network(
+        &mut self,
+        id: &str,
+    ) -> Result<Vec<Invalidation>, bollard::errors::Error> {
+        let network = found(
+            self.docker
+                .inspect_network(id, None::<InspectNetworkOptions<String>>)
+                .await,
+        )?;
+
+        let previous = self
+            .get::<Network>()
+            .into_iter()
+            .find(|network| network.get_id() == id)
+            .map(|network| (network.get_ino(), network.get_name().clone()));
+
+        let network = network.map(|network| {
+            let mut network = Network::new(network, &self.docker, &self.inodes);
+            if let Some(previous) = self
+                .mappings
+                .get(&network.get_ino())
+                .and_then(|child| child.as_network())
+            {
+                network.keep_state(previous);
+            }
+            Box::new(network) as Box<dyn ChildDirectory>
+        });
+
+        Ok(self.replace_child(ParentDirectories::Networks, previous, network))
+    }
+
+    // A container joined or left a network, both the network and the container list it.
+    pub async fn refresh_endpoint(
+        &mut self,
+        network: &str,
+        container: &str,
+    ) -> Result<Vec<Invalidation>, bollard::errors::Error> {
+        let name = self
+            .get::<Container>()
+            .into_iter()
+            .find(|candidate| candidate.get_id() == container)
+            .map(|container| container.get_name().clone());
+
+        let mut invalidations: Vec<Invalidation> = self
+            .get::<Network>()
+            .into_iter()
+            .filter(|candidate| candidate.get_id() == network)
+            .flat_map(|network| network.expire(name.as_deref()))
+            .collect();
+        invalidations.extend(self.refresh_container(container).await?);
+
+        Ok(invalidations)
     }
 
     pub fn get<T>(&self) -> Vec<&T>
@@ -0,0 +1,103 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bollard::{
+    service::{EventMessage, EventMessageTypeEnum},
+    system::EventsOptions,
+};
+use futures::StreamExt;
+use tokio::sync::{mpsc::UnboundedSender, Mutex};
+
+use crate::{docker_strategy::Docker, fuse_handler::Invalidation};
+
+// The daemon may be restarting, subscribing again is retried after a while.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Follows the events of the daemon, updating the mappings as objects change instead of listing
+// them again once their TTL expired, and dropping what the kernel cached about them. Listings
+// fall back to their TTL while the stream is down, containers keeping it all along.
+pub async fn follow(docker: Arc<Mutex<Docker>>, invalidations: UnboundedSender<Invalidation>) {
+    loop {
+        // Events since the subscription are replayed, none is missed while listing everything.
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let client = docker.lock().await.get_docker().clone();
+        let mut events = client.events(Some(EventsOptions::<String> {
+            since: Some(since.to_string()),
+            ..Default::default()
+        }));
+
+        match docker.lock().await.start_following_events().await {
+            Ok(()) => log::info!("Following events of the docker daemon"),
+            Err(e) => log::error!("Failed to update the mappings: {}", e),
+        }
+
+        while let Some(event) = events.next().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::error!("Failed to follow events of the docker daemon: {}", e);
+                    break;
+                }
+            };
+
+            for invalidation in apply(&docker, event).await {
+                if let Err(e) = invalidations.send(invalidation) {
+                    log::error!("Failed to queue invalidation: {}", e);
+                }
+            }
+        }
+
+        docker.lock().await.stop_following_events();
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+// Other actions, like `exec_start` or `attach`, don't change what is listed. Health checks report
+// their result in the action itself, as `health_status: healthy`.
+async fn apply(docker: &Mutex<Docker>, event: EventMessage) -> Vec<Invalidation> {
+    let action = event.action.unwrap_or_default();
+    let actor = event.actor.unwrap_or_default();
+    let id = actor.id.unwrap_or_default();
+    let attributes = actor.attributes.unwrap_or_default();
+
+    log::debug!("Event {:?} {} of {}", event.typ, action, id);
+
+    let mut docker = docker.lock().await;
+    let result = match (event.typ, action.as_str()) {
+        (
+            Some(EventMessageTypeEnum::CONTAINER),
+            "create" | "destroy" | "rename" | "start" | "restart" | "die" | "kill" | "oom"
+            | "pause" | "unpause" | "update",
+        ) => docker.refresh_container(&id).await,
+        (Some(EventMessageTypeEnum::CONTAINER), action) if action.starts_with("health_status") => {
+            docker.refresh_container(&id).await
+        }
+        (
+            Some(EventMessageTypeEnum::IMAGE),
+            "pull" | "tag" | "untag" | "delete" | "import" | "load",
+        ) => docker.refresh_images().await,
+        (Some(EventMessageTypeEnum::VOLUME), "create" | "destroy") => {
+            docker.refresh_volume(&id).await
+        }
+        (Some(EventMessageTypeEnum::NETWORK), "create" | "destroy") => {
+            docker.refresh_network(&id).await
+        }
+        (Some(EventMessageTypeEnum::NETWORK), "connect" | "disconnect") => {
+            match attributes.get("container") {
+                Some(container) => docker.refresh_endpoint(&id, container).await,
+                None => Ok(Vec::new()),
+            }
+        }
+        _ => Ok(Vec::new()),
+    };
+
+    result.unwrap_or_else(|e| {
+        log::error!("Failed to apply event {} of {}: {}", action, id, e);
+        Vec::new()
+    })
+}
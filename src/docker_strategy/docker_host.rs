@@ -0,0 +1,535 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bollard::API_DEFAULT_VERSION;
+
+// Seconds before requests to the daemon time out, as in bollard.
+const TIMEOUT: u64 = 120;
+
+// Context of the local daemon, whatever the docker client configuration says.
+const DEFAULT_CONTEXT: &str = "default";
+
+// Where bollard looks for the local daemon.
+const DEFAULT_SOCKET: &str = "unix:///var/run/docker.sock";
+
+// Socket of rootful podman, rootless podman listening in the runtime directory of the user.
+const PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+
+// How to reach the daemon, as given on the command line.
+#[derive(Debug, Default)]
+pub struct HostOptions {
+    pub host: Option<String>,
+    pub context: Option<String>,
+    pub tls_verify: bool,
+    pub tls_ca_cert: Option<PathBuf>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub podman: bool,
+}
+
+// Certificate of the authority the daemon is checked against, and the client key pair.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub ca: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    fn in_dir(dir: &Path) -> Self {
+        Self {
+            ca: dir.join("ca.pem"),
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        }
+    }
+}
+
+// Daemon to connect to, the local socket when there is no address.
+#[derive(Debug, Clone)]
+pub struct DockerHost {
+    address: Option<String>,
+    tls: Option<TlsFiles>,
+}
+
+impl Display for DockerHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.address, &self.tls) {
+            (None, _) => write!(f, "{}", DEFAULT_SOCKET),
+            (Some(address), None) => write!(f, "{}", address),
+            (Some(address), Some(_)) => write!(f, "{} over TLS", address),
+        }
+    }
+}
+
+impl DockerHost {
+    // Same precedence as the docker client: `--host`, `--context`, `DOCKER_HOST`,
+    // `DOCKER_CONTEXT`, then the current context of `config.json`. Podman is looked up in
+    // `CONTAINER_HOST`, then in its sockets, instead of the environment of the docker client.
+    pub fn resolve(options: &HostOptions) -> io::Result<Self> {
+        if let Some(host) = &options.host {
+            return Self::from_address(host, options);
+        }
+        if let Some(context) = &options.context {
+            return Self::from_context(context);
+        }
+        if options.podman {
+            let host = match non_empty_var("CONTAINER_HOST") {
+                Some(host) => host,
+                None => podman_socket()?,
+            };
+            return Self::from_address(&host, options);
+        }
+        if let Some(host) = non_empty_var("DOCKER_HOST") {
+            return Self::from_address(&host, options);
+        }
+
+        let context = match non_empty_var("DOCKER_CONTEXT") {
+            Some(context) => Some(context),
+            None => current_context()?,
+        };
+        match context {
+            Some(context) => Self::from_context(&context),
+            None => Ok(Self {
+                address: None,
+                tls: None,
+            }),
+        }
+    }
+
+    // TLS is used for `https://` addresses, and for `tcp://` ones once verification or any
+    // certificate is asked for. Certificates default to those of `DOCKER_CERT_PATH`, or of the
+    // configuration directory.
+    fn from_address(address: &str, options: &HostOptions) -> io::Result<Self> {
+        let wants_tls = options.tls_verify
+            || options.tls_ca_cert.is_some()
+            || options.tls_cert.is_some()
+            || options.tls_key.is_some();
+        let use_tls = match scheme(address)? {
+            "https" => true,
+            "tcp" => wants_tls,
+            _ => false,
+        };
+
+        let tls = if use_tls {
+            let defaults = TlsFiles::in_dir(&cert_dir()?);
+            Some(TlsFiles {
+                ca: options.tls_ca_cert.clone().unwrap_or(defaults.ca),
+                cert: options.tls_cert.clone().unwrap_or(defaults.cert),
+                key: options.tls_key.clone().unwrap_or(defaults.key),
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            address: Some(address.to_string()),
+            tls,
+        })
+    }
+
+    // Contexts are stored by the docker client in `contexts/meta/<digest>/meta.json`, their
+    // certificates in `contexts/tls/<digest>/docker`. Metadata are matched by name rather than
+    // by digest.
+    fn from_context(name: &str) -> io::Result<Self> {
+        if name == DEFAULT_CONTEXT {
+            return Ok(Self {
+                address: None,
+                tls: None,
+            });
+        }
+
+        let contexts = config_dir()?.join("contexts");
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Docker context {} not found", name),
+            )
+        };
+
+        let entries = match fs::read_dir(contexts.join("meta")) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let dir = entry?.path();
+            let meta: serde_json::Value = match fs::read(dir.join("meta.json")) {
+                Ok(meta) => serde_json::from_slice(&meta)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            if meta["Name"].as_str() != Some(name) {
+                continue;
+            }
+
+            let endpoint = &meta["Endpoints"]["docker"];
+            let address = endpoint["Host"].as_str().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Docker context {} has no docker endpoint", name),
+                )
+            })?;
+            scheme(address)?;
+            if endpoint["SkipTLSVerify"].as_bool() == Some(true) {
+                log::warn!("Verifying the daemon of context {} anyway", name);
+            }
+
+            let tls_dir = dir
+                .file_name()
+                .map(|digest| contexts.join("tls").join(digest).join("docker"));
+            let tls = tls_dir
+                .filter(|dir| dir.is_dir())
+                .map(|dir| TlsFiles::in_dir(&dir));
+
+            return Ok(Self {
+                address: Some(address.to_string()),
+                tls,
+            });
+        }
+
+        Err(not_found())
+    }
+
+    pub fn address(&self) -> &str {
+        self.address.as_deref().unwrap_or(DEFAULT_SOCKET)
+    }
+
+    // Only the default socket is known to be of a daemon of this host, other sockets may be
+    // forwarded from anywhere.
+    pub fn is_local(&self) -> bool {
+        self.address() == DEFAULT_SOCKET
+    }
+
+    // The API of podman is requested without bollard, over plain sockets only.
+    pub fn libpod_address(&self) -> io::Result<&str> {
+        let address = self.address();
+        if self.tls.is_some() || address.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "Can't list pods of {}, podman is only reached without TLS",
+                    self
+                ),
+            ));
+        }
+
+        Ok(address)
+    }
+
+    // Only sets the client up, nothing is sent to the daemon yet.
+    pub fn connect(&self) -> Result<bollard::Docker, bollard::errors::Error> {
+        let address = match &self.address {
+            Some(address) => address,
+            None => return bollard::Docker::connect_with_local_defaults(),
+        };
+
+        match (&self.tls, address.split_once("://")) {
+            (Some(tls), _) => bollard::Docker::connect_with_ssl(
+                address,
+                &tls.key,
+                &tls.cert,
+                &tls.ca,
+                TIMEOUT,
+                API_DEFAULT_VERSION,
+            ),
+            (None, Some(("unix", _))) => {
+                bollard::Docker::connect_with_unix(address, TIMEOUT, API_DEFAULT_VERSION)
+            }
+            (None, _) => bollard::Docker::connect_with_http(address, TIMEOUT, API_DEFAULT_VERSION),
+        }
+    }
+}
+
+// Scheme of an address bollard can reach. SSH is left to the docker client, the socket of the
+// remote daemon can be forwarded with `ssh -L` though.
+fn scheme(address: &str) -> io::Result<&str> {
+    match address.split_once("://") {
+        Some((scheme @ ("unix" | "tcp" | "http" | "https"), _)) => Ok(scheme),
+        Some(("ssh", _)) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Can't reach {} over SSH, forward the socket of the daemon with `ssh -L` and \
+                 mount it as unix://<socket> instead",
+                address
+            ),
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported docker host {}", address),
+        )),
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// `DOCKER_CONFIG`, or `~/.docker`.
+fn config_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = non_empty_var("DOCKER_CONFIG") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    non_empty_var("HOME")
+        .map(|home| PathBuf::from(home).join(".docker"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))
+}
+
+fn podman_socket() -> io::Result<String> {
+    non_empty_var("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("podman").join("podman.sock"))
+        .into_iter()
+        .chain([PathBuf::from(PODMAN_SOCKET)])
+        .find(|socket| socket.exists())
+        .map(|socket| format!("unix://{}", socket.display()))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "No podman socket found, is the podman.socket unit started ?",
+            )
+        })
+}
+
+fn cert_dir() -> io::Result<PathBuf> {
+    match non_empty_var("DOCKER_CERT_PATH") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => config_dir(),
+    }
+}
+
+// Without a configuration directory, the local daemon is used.
+fn current_context() -> io::Result<Option<String>> {
+    let dir = match config_dir() {
+        Ok(dir) => dir,
+        Err(_) => return Ok(None),
+    };
+
+    let config = match fs::read(dir.join("config.json")) {
+        Ok(config) => config,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let config: serde_json::Value = serde_json::from_slice(&config)?;
+
+    Ok(config["currentContext"]
+        .as_str()
+        .filter(|context| !context.is_empty())
+        .map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheme() {
+        assert_eq!(scheme("unix:///var/run/docker.sock").unwrap(), "unix");
+        assert_eq!(scheme("tcp://10.0.0.1:2376").unwrap(), "tcp");
+        assert_eq!(scheme("https://docker:2376").unwrap(), "https");
+        assert_eq!(
+            scheme("ssh://me@host").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+        assert_eq!(
+            scheme("/var/run/docker.sock").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    fn write_context(config: &Path, digest: &str, name: &str, host: &str, tls: bool) {
+        let meta = config.join("contexts").join("meta").join(digest);
+        fs::create_dir_all(&meta).unwrap();
+        let endpoints = serde_json::json!({ "docker": { "Host": host } });
+        let context = serde_json::json!({ "Name": name, "Endpoints": endpoints });
+        fs::write(meta.join("meta.json"), context.to_string()).unwrap();
+
+        if tls {
+            let tls = config.join("contexts").join("tls").join(digest);
+            fs::create_dir_all(tls.join("docker")).unwrap();
+        }
+    }
+
+    // The environment is shared by the whole process, every case depending on it runs here.
+    #[test]
+    fn test_resolve() {
+        let config =
+            std::env::temp_dir().join(format!("fuse_docker_config_{}", std::process::id()));
+        let runtime = config.join("runtime");
+        fs::create_dir_all(runtime.join("podman")).unwrap();
+        write_context(&config, "1", "remote", "tcp://remote:2376", true);
+        write_context(&config, "2", "plain", "tcp://plain:2375", false);
+        write_context(&config, "3", "tunnel", "ssh://me@remote", false);
+
+        for name in [
+            "DOCKER_HOST",
+            "DOCKER_CONTEXT",
+            "DOCKER_CERT_PATH",
+            "CONTAINER_HOST",
+        ] {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("DOCKER_CONFIG", &config);
+        std::env::set_var("XDG_RUNTIME_DIR", &runtime);
+
+        let resolve = |options: HostOptions| DockerHost::resolve(&options);
+
+        // Without anything configured, the local socket.
+        let host = resolve(HostOptions::default()).unwrap();
+        assert_eq!(host.address(), DEFAULT_SOCKET);
+        assert!(host.tls.is_none());
+
+        // `--host`, TLS being asked for on tcp:// hosts only.
+        let host = resolve(HostOptions {
+            host: Some(String::from("tcp://10.0.0.1:2376")),
+            tls_verify: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(host.address(), "tcp://10.0.0.1:2376");
+        assert_eq!(host.tls.unwrap().ca, config.join("ca.pem"));
+
+        let host = resolve(HostOptions {
+            host: Some(String::from("tcp://10.0.0.1:2375")),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(host.tls.is_none());
+
+        let host = resolve(HostOptions {
+            host: Some(String::from("https://10.0.0.1:2376")),
+            tls_key: Some(PathBuf::from("/keys/key.pem")),
+            ..Default::default()
+        })
+        .unwrap();
+        let tls = host.tls.unwrap();
+        assert_eq!(tls.key, PathBuf::from("/keys/key.pem"));
+        assert_eq!(tls.cert, config.join("cert.pem"));
+
+        // Contexts, with their own certificates.
+        let host = resolve(HostOptions {
+            context: Some(String::from("remote")),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(host.address(), "tcp://remote:2376");
+        let tls_dir = config.join("contexts").join("tls").join("1").join("docker");
+        assert_eq!(host.tls.unwrap().ca, tls_dir.join("ca.pem"));
+
+        let host = resolve(HostOptions {
+            context: Some(String::from("plain")),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(host.tls.is_none());
+
+        let error = |options: HostOptions| resolve(options).unwrap_err().kind();
+        assert_eq!(
+            error(HostOptions {
+                context: Some(String::from("missing")),
+                ..Default::default()
+            }),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            error(HostOptions {
+                context: Some(String::from("tunnel")),
+                ..Default::default()
+            }),
+            io::ErrorKind::Unsupported
+        );
+
+        // The current context of the configuration, `DOCKER_CONTEXT` then `DOCKER_HOST` coming
+        // first.
+        fs::write(config.join("config.json"), r#"{"currentContext": "plain"}"#).unwrap();
+        assert_eq!(
+            resolve(HostOptions::default()).unwrap().address(),
+            "tcp://plain:2375"
+        );
+
+        std::env::set_var("DOCKER_CONTEXT", "remote");
+        assert_eq!(
+            resolve(HostOptions::default()).unwrap().address(),
+            "tcp://remote:2376"
+        );
+
+        std::env::set_var("DOCKER_HOST", "unix:///run/user/docker.sock");
+        assert_eq!(
+            resolve(HostOptions::default()).unwrap().address(),
+            "unix:///run/user/docker.sock"
+        );
+
+        // `--context` comes before the environment.
+        let host = resolve(HostOptions {
+            context: Some(String::from(DEFAULT_CONTEXT)),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(host.address(), DEFAULT_SOCKET);
+
+        // Podman ignores the environment of the docker client.
+        let podman = || HostOptions {
+            podman: true,
+            ..Default::default()
+        };
+        if !Path::new(PODMAN_SOCKET).exists() {
+            assert_eq!(error(podman()), io::ErrorKind::NotFound);
+        }
+
+        let socket = runtime.join("podman").join("podman.sock");
+        fs::write(&socket, "").unwrap();
+        assert_eq!(
+            resolve(podman()).unwrap().address(),
+            format!("unix://{}", socket.display())
+        );
+
+        std::env::set_var("CONTAINER_HOST", "tcp://podman:8080");
+        assert_eq!(resolve(podman()).unwrap().address(), "tcp://podman:8080");
+
+        for name in [
+            "DOCKER_HOST",
+            "DOCKER_CONTEXT",
+            "DOCKER_CONFIG",
+            "CONTAINER_HOST",
+            "XDG_RUNTIME_DIR",
+        ] {
+            std::env::remove_var(name);
+        }
+        fs::remove_dir_all(config).unwrap();
+    }
+
+    #[test]
+    fn test_is_local() {
+        let host = |address: Option<&str>| DockerHost {
+            address: address.map(String::from),
+            tls: None,
+        };
+
+        assert!(host(None).is_local());
+        assert!(host(Some(DEFAULT_SOCKET)).is_local());
+        assert!(!host(Some("unix:///tmp/forwarded.sock")).is_local());
+        assert!(!host(Some("tcp://10.0.0.1:2375")).is_local());
+    }
+
+    #[test]
+    fn test_libpod_address() {
+        let host = |address: &str, tls: bool| DockerHost {
+            address: Some(address.to_string()),
+            tls: tls.then(|| TlsFiles::in_dir(Path::new("/certs"))),
+        };
+
+        assert_eq!(
+            host("unix:///run/podman/podman.sock", false)
+                .libpod_address()
+                .unwrap(),
+            "unix:///run/podman/podman.sock"
+        );
+        assert!(host("tcp://podman:8080", true).libpod_address().is_err());
+        assert!(host("https://podman:8080", false).libpod_address().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+// Options understood by the docker strategy, given as `-o` mount options.
+#[derive(Debug, Clone)]
+pub struct DockerOptions {
+    // Kill running containers when their directory is removed.
+    pub rmdir_force: bool,
+    // Remove the anonymous volumes of containers along with their directory.
+    pub rmdir_volumes: bool,
+    // Return from `mkdir` in the images directory without waiting for the pull to finish.
+    pub pull_async: bool,
+    // Whether the daemon runs on this host, so that its volumes can be reached in place.
+    pub local_daemon: bool,
+    // Image of the containers created to reach volumes the host can't read.
+    pub helper_image: String,
+    // Where image filesystems are exported and extracted.
+    pub cache_dir: PathBuf,
+    // Address of the podman service when mounting podman, which lists pods in its own API only.
+    pub podman: Option<String>,
+}
+
+impl Default for DockerOptions {
+    fn default() -> Self {
+        Self {
+            rmdir_force: false,
+            rmdir_volumes: false,
+            pull_async: false,
+            local_daemon: false,
+            helper_image: String::from("busybox:latest"),
+            cache_dir: std::env::temp_dir().join("fuse_docker"),
+            podman: None,
+        }
+    }
+}
+
+impl DockerOptions {
+    pub fn from_mount_options(options: &[String]) -> Self {
+        let mut docker_options = Self::default();
+
+        for option in options {
+            match (option.as_str(), option.split_once('=')) {
+                ("rmdir_force", _) => docker_options.rmdir_force = true,
+                ("rmdir_volumes", _) => docker_options.rmdir_volumes = true,
+                ("pull_async", _) => docker_options.pull_async = true,
+                (_, Some(("cache_dir", dir))) => docker_options.cache_dir = PathBuf::from(dir),
+                (_, Some(("helper_image", image))) => {
+                    docker_options.helper_image = image.to_string()
+                }
+                _ => log::warn!("Ignoring unknown mount option {}", option),
+            }
+        }
+
+        docker_options
+    }
+}
@@ -1,31 +1,97 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
 
-use fuser::Request;
+use fuser::{FileAttr, FileType, Request};
 use tokio::runtime::Handle;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc::UnboundedSender, Mutex};
 
+use crate::docker_strategy::child_directories::child_directories::{join_path, ChildDirectory};
+use crate::docker_strategy::docker_events;
+use crate::docker_strategy::file_handle::FileHandle;
 use crate::docker_strategy::parent_directories::ParentDirectories;
-use crate::fuse_handler::FileSystemStrategy;
+use crate::docker_strategy::DockerOptions;
+use crate::fuse_handler::{DirectoryEntry, FileSystemStrategy, Invalidation, OpenedFile};
 
 pub struct DockerStrategy {
     docker: Arc<Mutex<super::Docker>>,
+    handles: std::sync::Mutex<HashMap<u64, Arc<FileHandle>>>,
+    next_fh: AtomicU64,
+    invalidations: UnboundedSender<Invalidation>,
 }
 
 pub enum DockerError {
     UnknownParentDirectory,
+    UnknownChildEntry,
+    InvalidCommand,
 }
 
 impl DockerStrategy {
-    pub fn new() -> Self {
-        let docker = super::Docker::new();
+    pub fn new(
+        docker: bollard::Docker,
+        options: DockerOptions,
+        invalidations: UnboundedSender<Invalidation>,
+    ) -> Self {
+        let docker = Arc::new(Mutex::new(super::Docker::new(docker, options)));
+        Handle::current().spawn(docker_events::follow(docker.clone(), invalidations.clone()));
 
         log::info!(target: "Docker", "DockerStrategy initialized");
 
         Self {
-            docker: Arc::new(Mutex::new(docker)),
+            docker,
+            handles: std::sync::Mutex::new(HashMap::new()),
+            next_fh: AtomicU64::new(1),
+            invalidations,
         }
     }
+
+    fn invalidate_entry(&self, parent: u64, name: &std::ffi::OsStr) {
+        let invalidation = Invalidation::Entry {
+            parent,
+            name: name.to_os_string(),
+        };
+
+        if let Err(e) = self.invalidations.send(invalidation) {
+            log::error!("Failed to queue invalidation: {}", e);
+        }
+    }
+
+    fn insert_handle(&self, handle: FileHandle) -> OpenedFile {
+        let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+        let flags = handle.open_flags();
+        self.handles.lock().unwrap().insert(fh, Arc::new(handle));
+
+        OpenedFile { fh, flags }
+    }
+
+    // Child directories are taken out of the mappings along with a client, so that downloads
+    // and other calls to the daemon made by them don't keep the mappings locked.
+    fn resolve(
+        &self,
+        ino: u64,
+    ) -> Result<(Arc<dyn ChildDirectory>, String, bollard::Docker), libc::c_int> {
+        let docker = self.docker.blocking_lock();
+        let (child, path) = docker.resolve(ino).ok_or(libc::ENOENT)?;
+
+        Ok((child, path, docker.get_docker().clone()))
+    }
+
+    fn remove(&self, parent: u64, name: &std::ffi::OsStr) -> Result<(), libc::c_int> {
+        let name = name.to_str().ok_or(libc::ENOENT)?;
+        let (child, path, _) = self.resolve(parent)?;
+
+        child.remove(&join_path(&path, name))
+    }
+}
+
+fn access_allowed(attr: &FileAttr, flags: i32) -> bool {
+    let (read, write) = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => (true, false),
+        libc::O_WRONLY => (false, true),
+        _ => (true, true),
+    };
+
+    (!read || attr.perm & 0o444 != 0) && (!write || attr.perm & 0o222 != 0)
 }
 
 impl FileSystemStrategy for DockerStrategy {
@@ -45,65 +111,205 @@ impl FileSystemStrategy for DockerStrategy {
         Ok(())
     }
 
-    fn lookup(
-        &self,
-        _req: &fuser::Request<'_>,
-        parent: u64,
-        name: &std::ffi::OsStr,
-        reply: fuser::ReplyEntry,
-    ) -> Result<(), libc::c_int> {
+    fn lookup(&self, parent: u64, name: &std::ffi::OsStr) -> Result<FileAttr, libc::c_int> {
         if let Ok(parent) = ParentDirectories::try_from(parent) {
             log::debug!("lookup: parent: {:?}, name: {:?}", parent, name);
-            Handle::current().block_on(parent.lookup(name, reply, self.docker.clone()))
-        } else {
-            reply.error(libc::ENOENT);
-            Ok(())
+            return Handle::current().block_on(parent.lookup(name, self.docker.clone()));
+        }
+
+        let name = name.to_str().ok_or(libc::ENOENT)?;
+        let (child, path, client) = self.resolve(parent)?;
+        let attr = Handle::current().block_on(child.lookup(&path, name, &client))?;
+
+        self.docker.blocking_lock().remember(attr.ino);
+        Ok(attr)
+    }
+
+    fn forget(&self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        self.docker.blocking_lock().forget(ino, nlookup);
+    }
+
+    fn open(&self, ino: u64, flags: i32) -> Result<OpenedFile, libc::c_int> {
+        let (child, path, client) = self.resolve(ino)?;
+
+        let handle = match child.getattr(&path) {
+            Ok(attr) if attr.kind != FileType::RegularFile => Err(libc::EISDIR),
+            Ok(attr) if !access_allowed(&attr, flags) => Err(libc::EACCES),
+            Ok(_) => Handle::current().block_on(child.open(&path, &client)),
+            Err(e) => Err(e),
+        }?;
+
+        Ok(self.insert_handle(handle))
+    }
+
+    fn getattr(&self, _req: &Request<'_>, ino: u64) -> Result<FileAttr, libc::c_int> {
+        if let Ok(parent) = ParentDirectories::try_from(ino) {
+            return Ok(parent.attr());
+        }
+
+        let (child, path, _) = self.resolve(ino)?;
+        child.getattr(&path)
+    }
+
+    fn setattr(&self, ino: u64, size: Option<u64>) -> Result<FileAttr, libc::c_int> {
+        let (child, path, _) = self.resolve(ino)?;
+        match size {
+            Some(size) => child.set_size(&path, size),
+            None => child.getattr(&path),
+        }
+    }
+
+    fn readdir(&self, ino: u64, _fh: u64) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        if let Ok(parent) = ParentDirectories::try_from(ino) {
+            return Handle::current().block_on(parent.read_dir(self.docker.clone()));
+        }
+
+        let (child, path, client) = self.resolve(ino)?;
+        Handle::current().block_on(child.read_dir(&path, &client))
+    }
+
+    fn unlink(&self, parent: u64, name: &std::ffi::OsStr) -> Result<(), libc::c_int> {
+        match ParentDirectories::try_from(parent) {
+            Ok(parent_directory) => {
+                Handle::current().block_on(parent_directory.unlink(name, self.docker.clone()))?;
+                self.invalidate_entry(parent, name);
+                Ok(())
+            }
+            Err(_) => self.remove(parent, name),
         }
     }
 
-    fn open(
+    fn symlink(
         &self,
-        _req: &fuser::Request<'_>,
-        _ino: u64,
-        _flags: i32,
-        reply: fuser::ReplyOpen,
-    ) -> Result<(), libc::c_int> {
-        reply.opened(0, 0);
-        Ok(())
+        parent: u64,
+        name: &std::ffi::OsStr,
+        link: &std::path::Path,
+    ) -> Result<FileAttr, libc::c_int> {
+        let attr = match ParentDirectories::try_from(parent) {
+            Ok(parent) => {
+                Handle::current().block_on(parent.symlink(name, link, self.docker.clone()))
+            }
+            Err(_) => {
+                let name = name.to_str().ok_or(libc::EINVAL)?;
+                let (child, path, _) = self.resolve(parent)?;
+                child.symlink(&join_path(&path, name), link)
+            }
+        }?;
+
+        self.docker.blocking_lock().remember(attr.ino);
+        Ok(attr)
+    }
+
+    fn mkdir(&self, parent: u64, name: &std::ffi::OsStr) -> Result<FileAttr, libc::c_int> {
+        let attr = match ParentDirectories::try_from(parent) {
+            Ok(parent) => Handle::current().block_on(parent.mkdir(name, self.docker.clone())),
+            Err(_) => {
+                let name = name.to_str().ok_or(libc::EINVAL)?;
+                let (child, path, _) = self.resolve(parent)?;
+                child.mkdir(&join_path(&path, name))
+            }
+        }?;
+
+        self.docker.blocking_lock().remember(attr.ino);
+        Ok(attr)
     }
 
-    fn getattr(
+    // Only volume data accepts new files, but shells open existing files with `O_CREAT` when
+    // redirecting into them.
+    fn create(
         &self,
-        _req: &Request<'_>,
-        ino: u64,
-        reply: fuser::ReplyAttr,
-    ) -> Result<(), libc::c_int> {
-        if let Ok(parent) = ParentDirectories::try_from(ino) {
-            reply.attr(&Duration::from_secs(1), &parent.attr());
-            Ok(())
-        } else if let Some(child) = self.docker.blocking_lock().get_child(ino) {
-            reply.attr(&Duration::from_secs(1), &child.getattr());
-            Ok(())
-        } else {
-            reply.error(libc::ENOENT);
-            Ok(())
+        parent: u64,
+        name: &std::ffi::OsStr,
+        flags: i32,
+    ) -> Result<(FileAttr, OpenedFile), libc::c_int> {
+        let name = match (ParentDirectories::try_from(parent), name.to_str()) {
+            (Err(_), Some(name)) => name,
+            _ => return Err(libc::EACCES),
+        };
+
+        let (child, path, client) = self.resolve(parent)?;
+        let entry_path = join_path(&path, name);
+
+        let attr = match Handle::current().block_on(child.lookup(&path, name, &client)) {
+            Err(libc::ENOENT) => child.create(&entry_path),
+            attr => attr,
         }
+        .and_then(|attr| match attr.kind {
+            FileType::RegularFile if access_allowed(&attr, flags) => Ok(attr),
+            FileType::RegularFile => Err(libc::EACCES),
+            _ => Err(libc::EISDIR),
+        })?;
+        let handle = Handle::current().block_on(child.open(&entry_path, &client))?;
+
+        self.docker.blocking_lock().remember(attr.ino);
+        Ok((attr, self.insert_handle(handle)))
     }
 
-    fn readdir(
+    fn rmdir(&self, parent: u64, name: &std::ffi::OsStr) -> Result<(), libc::c_int> {
+        match ParentDirectories::try_from(parent) {
+            Ok(parent_directory) => {
+                Handle::current().block_on(parent_directory.rmdir(name, self.docker.clone()))?;
+                self.invalidate_entry(parent, name);
+                Ok(())
+            }
+            Err(_) => self.remove(parent, name),
+        }
+    }
+
+    // Files can only be moved inside of the directory of a single docker object, always
+    // replacing the target. Neither `RENAME_NOREPLACE` nor `RENAME_EXCHANGE` can be honored by
+    // the helpers of volumes.
+    fn rename(
         &self,
-        _req: &Request<'_>,
-        ino: u64,
-        _fh: u64,
-        offset: i64,
-        reply: &mut fuser::ReplyDirectory,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        newparent: u64,
+        newname: &std::ffi::OsStr,
+        flags: u32,
     ) -> Result<(), libc::c_int> {
-        if let Ok(parent) = ParentDirectories::try_from(ino) {
-            Handle::current().block_on(parent.read_dir(offset, reply, self.docker.clone()))
-        } else if let Some(child) = self.docker.blocking_lock().get_child(ino) {
-            child.read_dir(offset, reply)
-        } else {
-            Err(libc::ENOENT)
+        if flags != 0 {
+            return Err(libc::EINVAL);
+        }
+        if ParentDirectories::try_from(parent).is_ok()
+            || ParentDirectories::try_from(newparent).is_ok()
+        {
+            return Err(libc::EPERM);
         }
+
+        let name = name.to_str().ok_or(libc::ENOENT)?;
+        let newname = newname.to_str().ok_or(libc::EINVAL)?;
+
+        let (child, path, _) = self.resolve(parent)?;
+        let (new_child, new_path, _) = self.resolve(newparent)?;
+        if child.get_ino() != new_child.get_ino() {
+            return Err(libc::EXDEV);
+        }
+
+        child.rename(&join_path(&path, name), &join_path(&new_path, newname))
+    }
+
+    fn readlink(&self, _req: &Request<'_>, ino: u64) -> Result<String, libc::c_int> {
+        let (child, path, _) = self.resolve(ino)?;
+        child.readlink(&path)
+    }
+
+    fn read(&self, _ino: u64, fh: u64, offset: i64, size: u32) -> Result<Vec<u8>, libc::c_int> {
+        let handle = self.handles.lock().unwrap().get(&fh).cloned();
+        handle.ok_or(libc::EBADF)?.read(offset, size)
+    }
+
+    fn write(&self, _ino: u64, fh: u64, offset: i64, data: &[u8]) -> Result<u32, libc::c_int> {
+        let handle = self.handles.lock().unwrap().get(&fh).cloned();
+        handle.ok_or(libc::EBADF)?.write(offset, data)
+    }
+
+    fn flush(&self, _ino: u64, fh: u64) -> Result<(), libc::c_int> {
+        let handle = self.handles.lock().unwrap().get(&fh).cloned();
+        handle.ok_or(libc::EBADF)?.flush()
+    }
+
+    fn release(&self, _req: &Request<'_>, _ino: u64, fh: u64) -> Result<(), libc::c_int> {
+        self.handles.lock().unwrap().remove(&fh);
+        Ok(())
     }
 }
@@ -0,0 +1,377 @@
+use std::{ffi::OsStr, path::Path};
+
+use fuser::{FileAttr, FileType, Request};
+use tokio::{
+    runtime::Handle,
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+
+use crate::{
+    docker_strategy::{parent_directories::ParentDirectories, DockerOptions, DockerStrategy},
+    fuse_handler::{DirectoryEntry, FileSystemStrategy, Invalidation, OpenedFile},
+};
+
+const ROOT: u64 = ParentDirectories::Root as u64;
+
+// Inodes of an engine are moved above this bit, the engine being numbered from 1 in the bits
+// above it, so that the fixed inodes of `ParentDirectories` are allocated once per engine.
+const ENGINE_SHIFT: u32 = 48;
+
+// Mounts several engines side by side, the root listing one directory per engine named after
+// it, each one holding the tree of its own `DockerStrategy`.
+pub struct EnginesStrategy {
+    engines: Vec<(String, DockerStrategy)>,
+}
+
+impl EnginesStrategy {
+    pub fn new(
+        engines: Vec<(String, bollard::Docker, DockerOptions)>,
+        invalidations: UnboundedSender<Invalidation>,
+    ) -> Self {
+        let engines = engines
+            .into_iter()
+            .enumerate()
+            .map(|(engine, (name, docker, options))| {
+                let (sender, mut receiver) = unbounded_channel::<Invalidation>();
+                let invalidations = invalidations.clone();
+                Handle::current().spawn(async move {
+                    while let Some(invalidation) = receiver.recv().await {
+                        let invalidation = invalidation.map_inodes(|ino| Self::global(engine, ino));
+                        if invalidations.send(invalidation).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                log::info!("Mounting engine {}", name);
+                (name, DockerStrategy::new(docker, options, sender))
+            })
+            .collect();
+
+        Self { engines }
+    }
+
+    fn global(engine: usize, ino: u64) -> u64 {
+        ((engine as u64 + 1) << ENGINE_SHIFT) | ino
+    }
+
+    // The engine owning an inode and the inode inside of it, the root belonging to none.
+    fn local(&self, ino: u64) -> Result<(usize, &DockerStrategy, u64), libc::c_int> {
+        let engine = (ino >> ENGINE_SHIFT).checked_sub(1).ok_or(libc::ENOENT)? as usize;
+        let (_, strategy) = self.engines.get(engine).ok_or(libc::ENOENT)?;
+
+        Ok((engine, strategy, ino & ((1 << ENGINE_SHIFT) - 1)))
+    }
+
+    fn attr(engine: usize, attr: FileAttr) -> FileAttr {
+        FileAttr {
+            ino: Self::global(engine, attr.ino),
+            ..attr
+        }
+    }
+
+    fn root_attr(&self) -> FileAttr {
+        FileAttr {
+            size: self.engines.len() as u64,
+            ..ParentDirectories::Root.attr()
+        }
+    }
+
+    fn root_read_dir(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(ROOT, FileType::Directory, "."),
+            DirectoryEntry::new(ROOT, FileType::Directory, ".."),
+        ];
+
+        entries.extend(self.engines.iter().enumerate().map(|(engine, (name, _))| {
+            DirectoryEntry::new(Self::global(engine, ROOT), FileType::Directory, name)
+        }));
+
+        entries
+    }
+}
+
+impl FileSystemStrategy for EnginesStrategy {
+    // Engines out of reach are reported, the others are still mounted.
+    fn init(&self) -> Result<(), libc::c_int> {
+        let reachable = self
+            .engines
+            .iter()
+            .filter(|(name, strategy)| match strategy.init() {
+                Ok(()) => true,
+                Err(_) => {
+                    log::error!("Engine {} is out of reach", name);
+                    false
+                }
+            })
+            .count();
+
+        if reachable == 0 {
+            return Err(libc::EACCES);
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, parent: u64, name: &OsStr) -> Result<FileAttr, libc::c_int> {
+        if parent == ROOT {
+            let engine = self
+                .engines
+                .iter()
+                .position(|(engine_name, _)| OsStr::new(engine_name) == name)
+                .ok_or(libc::ENOENT)?;
+            return Ok(Self::attr(engine, ParentDirectories::Root.attr()));
+        }
+
+        let (engine, strategy, parent) = self.local(parent)?;
+        strategy
+            .lookup(parent, name)
+            .map(|attr| Self::attr(engine, attr))
+    }
+
+    fn forget(&self, req: &Request<'_>, ino: u64, nlookup: u64) {
+        if let Ok((_, strategy, ino)) = self.local(ino) {
+            strategy.forget(req, ino, nlookup);
+        }
+    }
+
+    fn open(&self, ino: u64, flags: i32) -> Result<OpenedFile, libc::c_int> {
+        if ino == ROOT {
+            return Err(libc::EISDIR);
+        }
+
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.open(ino, flags)
+    }
+
+    fn getattr(&self, req: &Request<'_>, ino: u64) -> Result<FileAttr, libc::c_int> {
+        if ino == ROOT {
+            return Ok(self.root_attr());
+        }
+
+        let (engine, strategy, ino) = self.local(ino)?;
+        strategy
+            .getattr(req, ino)
+            .map(|attr| Self::attr(engine, attr))
+    }
+
+    fn setattr(&self, ino: u64, size: Option<u64>) -> Result<FileAttr, libc::c_int> {
+        if ino == ROOT {
+            return Err(libc::ENOENT);
+        }
+
+        let (engine, strategy, ino) = self.local(ino)?;
+        strategy
+            .setattr(ino, size)
+            .map(|attr| Self::attr(engine, attr))
+    }
+
+    // The parent of an engine directory is the root of the mount.
+    fn readdir(&self, ino: u64, fh: u64) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        if ino == ROOT {
+            return Ok(self.root_read_dir());
+        }
+
+        let (engine, strategy, ino) = self.local(ino)?;
+        let entries = strategy.readdir(ino, fh)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|entry| DirectoryEntry {
+                ino: match (ino, entry.name.as_str()) {
+                    (ROOT, "..") => ROOT,
+                    _ => Self::global(engine, entry.ino),
+                },
+                ..entry
+            })
+            .collect())
+    }
+
+    fn unlink(&self, parent: u64, name: &OsStr) -> Result<(), libc::c_int> {
+        if parent == ROOT {
+            return Err(libc::EPERM);
+        }
+
+        let (_, strategy, parent) = self.local(parent)?;
+        strategy.unlink(parent, name)
+    }
+
+    fn symlink(&self, parent: u64, name: &OsStr, link: &Path) -> Result<FileAttr, libc::c_int> {
+        if parent == ROOT {
+            return Err(libc::EPERM);
+        }
+
+        let (engine, strategy, parent) = self.local(parent)?;
+        strategy
+            .symlink(parent, name, link)
+            .map(|attr| Self::attr(engine, attr))
+    }
+
+    // Engines come from the command line, they can't be added nor removed.
+    fn mkdir(&self, parent: u64, name: &OsStr) -> Result<FileAttr, libc::c_int> {
+        if parent == ROOT {
+            return Err(libc::EPERM);
+        }
+
+        let (engine, strategy, parent) = self.local(parent)?;
+        strategy
+            .mkdir(parent, name)
+            .map(|attr| Self::attr(engine, attr))
+    }
+
+    fn create(
+        &self,
+        parent: u64,
+        name: &OsStr,
+        flags: i32,
+    ) -> Result<(FileAttr, OpenedFile), libc::c_int> {
+        if parent == ROOT {
+            return Err(libc::EACCES);
+        }
+
+        let (engine, strategy, parent) = self.local(parent)?;
+        strategy
+            .create(parent, name, flags)
+            .map(|(attr, opened)| (Self::attr(engine, attr), opened))
+    }
+
+    fn rmdir(&self, parent: u64, name: &OsStr) -> Result<(), libc::c_int> {
+        if parent == ROOT {
+            return Err(libc::EPERM);
+        }
+
+        let (_, strategy, parent) = self.local(parent)?;
+        strategy.rmdir(parent, name)
+    }
+
+    fn rename(
+        &self,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+    ) -> Result<(), libc::c_int> {
+        if parent == ROOT || newparent == ROOT {
+            return Err(libc::EPERM);
+        }
+
+        let (engine, strategy, parent) = self.local(parent)?;
+        let (new_engine, _, newparent) = self.local(newparent)?;
+        if engine != new_engine {
+            return Err(libc::EXDEV);
+        }
+
+        strategy.rename(parent, name, newparent, newname, flags)
+    }
+
+    fn readlink(&self, req: &Request<'_>, ino: u64) -> Result<String, libc::c_int> {
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.readlink(req, ino)
+    }
+
+    // File handles are numbered by each engine, the inode telling which one opened it.
+    fn read(&self, ino: u64, fh: u64, offset: i64, size: u32) -> Result<Vec<u8>, libc::c_int> {
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.read(ino, fh, offset, size)
+    }
+
+    fn write(&self, ino: u64, fh: u64, offset: i64, data: &[u8]) -> Result<u32, libc::c_int> {
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.write(ino, fh, offset, data)
+    }
+
+    fn flush(&self, ino: u64, fh: u64) -> Result<(), libc::c_int> {
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.flush(ino, fh)
+    }
+
+    fn release(&self, req: &Request<'_>, ino: u64, fh: u64) -> Result<(), libc::c_int> {
+        let (_, strategy, ino) = self.local(ino)?;
+        strategy.release(req, ino, fh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Strategies of daemons out of reach, which only log their failures.
+    fn engines(names: &[&str]) -> EnginesStrategy {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let (invalidations, _) = unbounded_channel();
+
+        let engines = names
+            .iter()
+            .map(|name| {
+                let docker = bollard::Docker::connect_with_http(
+                    "http://127.0.0.1:1",
+                    1,
+                    bollard::API_DEFAULT_VERSION,
+                )
+                .unwrap();
+                (name.to_string(), docker, DockerOptions::default())
+            })
+            .collect();
+        let engines = EnginesStrategy::new(engines, invalidations);
+
+        // The tasks following events are dropped along with the runtime.
+        runtime.shutdown_background();
+        engines
+    }
+
+    #[test]
+    fn test_global_and_local_inodes() {
+        let engines = engines(&["local", "remote"]);
+
+        for (engine, ino) in [
+            (0, ROOT),
+            (0, 1234),
+            (1, ROOT),
+            (1, (1 << ENGINE_SHIFT) - 1),
+        ] {
+            let global = EnginesStrategy::global(engine, ino);
+            assert!(global >> ENGINE_SHIFT > 0);
+
+            let (local_engine, _, local) = engines.local(global).unwrap();
+            assert_eq!((local_engine, local), (engine, ino));
+        }
+        assert_ne!(
+            EnginesStrategy::global(0, ROOT),
+            EnginesStrategy::global(1, ROOT)
+        );
+    }
+
+    #[test]
+    fn test_local_inodes_out_of_engines() {
+        let engines = engines(&["local"]);
+
+        assert_eq!(engines.local(ROOT).err(), Some(libc::ENOENT));
+        assert_eq!(engines.local(1234).err(), Some(libc::ENOENT));
+        assert_eq!(
+            engines.local(EnginesStrategy::global(1, ROOT)).err(),
+            Some(libc::ENOENT)
+        );
+    }
+
+    #[test]
+    fn test_root_lists_engines() {
+        let engines = engines(&["local", "remote"]);
+
+        let entries: Vec<(u64, String)> = engines
+            .root_read_dir()
+            .into_iter()
+            .map(|entry| (entry.ino, entry.name))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (ROOT, String::from(".")),
+                (ROOT, String::from("..")),
+                (EnginesStrategy::global(0, ROOT), String::from("local")),
+                (EnginesStrategy::global(1, ROOT), String::from("remote")),
+            ]
+        );
+        assert_eq!(engines.root_attr().size, 2);
+    }
+}
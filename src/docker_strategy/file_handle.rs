@@ -0,0 +1,121 @@
+use std::{
+    fs::File,
+    os::unix::fs::FileExt,
+    sync::{Arc, Mutex},
+};
+
+use tokio::runtime::Handle;
+
+use crate::docker_strategy::{
+    containers::{ContainerCommand, DraftState, LogStream},
+    volumes::VolumeUpload,
+};
+
+// State kept by the kernel between `open` and `release`, so that every `read` on the same
+// handle sees the same content even when the file spans several `read` calls. Writes kept in
+// memory are applied by `flush`.
+#[derive(Debug)]
+pub enum FileHandle {
+    Snapshot(Arc<Vec<u8>>),
+    Buffer(Arc<Mutex<Vec<u8>>>),
+    Disk(File),
+    Upload(Arc<VolumeUpload>),
+    Logs {
+        stream: Arc<LogStream>,
+        last_eof: Mutex<Option<i64>>,
+    },
+    Control {
+        docker: bollard::Docker,
+        id: String,
+    },
+    DraftControl {
+        docker: bollard::Docker,
+        draft: Arc<DraftState>,
+    },
+}
+
+impl FileHandle {
+    pub fn read(&self, offset: i64, size: u32) -> Result<Vec<u8>, libc::c_int> {
+        match self {
+            FileHandle::Snapshot(content) => {
+                let start = (offset.max(0) as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+
+                Ok(content[start..end].to_vec())
+            }
+            FileHandle::Buffer(content) => {
+                let content = content.lock().unwrap();
+                let start = (offset.max(0) as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+
+                Ok(content[start..end].to_vec())
+            }
+            FileHandle::Disk(file) => {
+                let mut data = vec![0; size as usize];
+                let mut read = 0;
+                while read < data.len() {
+                    match file.read_at(&mut data[read..], offset.max(0) as u64 + read as u64) {
+                        Ok(0) => break,
+                        Ok(n) => read += n,
+                        Err(e) => return Err(e.raw_os_error().unwrap_or(libc::EIO)),
+                    }
+                }
+                data.truncate(read);
+
+                Ok(data)
+            }
+            FileHandle::Upload(upload) => Ok(upload.read(offset, size)),
+            FileHandle::Logs { stream, last_eof } => stream.read(offset, size, last_eof),
+            FileHandle::Control { .. } | FileHandle::DraftControl { .. } => Err(libc::EBADF),
+        }
+    }
+
+    pub fn write(&self, offset: i64, data: &[u8]) -> Result<u32, libc::c_int> {
+        match self {
+            FileHandle::Buffer(content) => {
+                let mut content = content.lock().unwrap();
+                let start = offset.max(0) as usize;
+                if content.len() < start + data.len() {
+                    content.resize(start + data.len(), 0);
+                }
+                content[start..start + data.len()].copy_from_slice(data);
+
+                Ok(data.len() as u32)
+            }
+            FileHandle::Disk(file) => file
+                .write_all_at(data, offset.max(0) as u64)
+                .map(|_| data.len() as u32)
+                .map_err(|e| e.raw_os_error().unwrap_or(libc::EIO)),
+            FileHandle::Upload(upload) => Ok(upload.write(offset, data)),
+            FileHandle::Control { docker, id } => {
+                Handle::current().block_on(ContainerCommand::run_all(docker, id, data))?;
+                Ok(data.len() as u32)
+            }
+            FileHandle::DraftControl { docker, draft } => {
+                Handle::current().block_on(draft.run_all(docker, data))?;
+                Ok(data.len() as u32)
+            }
+            _ => Err(libc::EBADF),
+        }
+    }
+
+    pub fn flush(&self) -> Result<(), libc::c_int> {
+        match self {
+            FileHandle::Upload(upload) => upload.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    // The size reported by `getattr` can't be trusted for content generated on open.
+    pub fn open_flags(&self) -> u32 {
+        match self {
+            FileHandle::Disk(_) => 0,
+            FileHandle::Snapshot(_)
+            | FileHandle::Buffer(_)
+            | FileHandle::Upload(_)
+            | FileHandle::Logs { .. }
+            | FileHandle::Control { .. }
+            | FileHandle::DraftControl { .. } => fuser::consts::FOPEN_DIRECT_IO,
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use bollard::service::ImageSummary;
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::OnceCell;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        file_handle::FileHandle,
+        images::{
+            image_pull::PULL_PROGRESS, images_by_id::BY_ID, ImageDetails, ImageFile, ImageRootFs,
+            PullState,
+        },
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const LAYERS: &str = "layers";
+const ROOTFS: &str = "rootfs";
+// Prefix of the paths given to the tags of an image, which no entry of its directory can have.
+const TAGS: &str = "/tags/";
+// Path given to the link of a dangling image in the `untagged` group.
+const UNTAGGED_LINK: &str = "/untagged";
+// Length of the ids the docker client shows.
+const SHORT_ID: usize = 12;
+
+// Tags of dangling images.
+pub const NONE_TAG: &str = "<none>:<none>";
+
+// Repositories may contain slashes, which can't be part of a file name.
+pub fn escape_name(name: &str) -> String {
+    name.replace('%', "%25").replace('/', "%2F")
+}
+
+pub fn unescape_name(name: &str) -> String {
+    name.replace("%2F", "/").replace("%25", "%")
+}
+
+// Splits `repo:tag`, the repository possibly holding the port of its registry.
+pub fn split_reference(reference: &str) -> Option<(&str, &str)> {
+    reference
+        .rsplit_once(':')
+        .filter(|(repo, tag)| !repo.is_empty() && !tag.is_empty() && !tag.contains('/'))
+}
+
+// Name the daemon lists a reference of Docker Hub under, `docker.io/library/nginx:latest` being
+// listed as `nginx:latest` by docker while podman keeps it whole.
+pub fn normalize_reference(reference: &str) -> &str {
+    let name = ["docker.io/", "index.docker.io/"]
+        .iter()
+        .find_map(|registry| reference.strip_prefix(registry))
+        .unwrap_or(reference);
+
+    match name.strip_prefix("library/") {
+        Some(official) if !official.contains('/') => official,
+        _ => name,
+    }
+}
+
+pub fn digest(id: &str) -> &str {
+    id.trim_start_matches("sha256:")
+}
+
+// An image is listed once by its id in the `by-id` group, its tags being symlinks to it listed
+// as `repo:tag` in the images directory. Dangling images are linked from the `untagged` group
+// instead.
+#[derive(Debug)]
+pub struct Image {
+    ino: u64,
+    parent_ino: u64,
+    name: String,
+    tags: Vec<String>,
+    pub image: ImageSummary,
+    details: Arc<OnceCell<Arc<ImageDetails>>>,
+    rootfs: Arc<OnceCell<Arc<ImageRootFs>>>,
+    cache_dir: PathBuf,
+    pull: Option<Arc<PullState>>,
+    inodes: Inodes,
+}
+
+impl From<Image> for Box<dyn ChildDirectory> {
+    fn from(value: Image) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for Image {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.image.id
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Images
+    }
+
+    fn as_image(&self) -> Option<&Image> {
+        Some(self)
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if let Some(rootfs_path) = Self::rootfs_path(path) {
+                return self.rootfs_entries(path, rootfs_path, docker).await;
+            }
+
+            match path {
+                "" => Ok(self.entries()),
+                LAYERS => Ok(self.layer_entries(&*self.details(docker).await?)),
+                _ => Err(libc::ENOTDIR),
+            }
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            let path = join_path(path, name);
+
+            // Sizes of every entry come from the details, or from the exported filesystem.
+            match Self::rootfs_path(&path) {
+                _ if path == PULL_PROGRESS => (),
+                Some("") => (),
+                Some(_) => {
+                    self.rootfs(docker).await?;
+                }
+                None => {
+                    self.details(docker).await?;
+                }
+            }
+
+            self.getattr(&path)
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        if path.is_empty() {
+            return Ok(self.dir_attr());
+        }
+
+        if let (PULL_PROGRESS, Some(pull)) = (path, &self.pull) {
+            return Ok(pull.progress_attr(FileAttr {
+                ino: self.entry_ino(path),
+                ..self.dir_attr()
+            }));
+        }
+
+        if let Some(tag) = path.strip_prefix(TAGS) {
+            return self.tag_attr(tag).ok_or(libc::ENOENT);
+        }
+        if path == UNTAGGED_LINK {
+            return self.untagged_attr().ok_or(libc::ENOENT);
+        }
+
+        match Self::rootfs_path(path) {
+            Some(rootfs_path) if !rootfs_path.is_empty() => {
+                let rootfs = self.rootfs.get().ok_or(libc::ENOENT)?;
+                let node = rootfs.get(rootfs_path).ok_or(libc::ENOENT)?;
+                return Ok(node.attr(self.entry_ino(path)));
+            }
+            _ if path == LAYERS || path == ROOTFS => {
+                return Ok(FileAttr {
+                    ino: self.entry_ino(path),
+                    ..self.dir_attr()
+                });
+            }
+            _ => (),
+        }
+
+        let size = match self.details.get() {
+            Some(details) => self.content(details, path)?.len() as u64,
+            None if self.is_file(path) => 0,
+            None => return Err(libc::ENOENT),
+        };
+
+        Ok(FileAttr {
+            ino: self.entry_ino(path),
+            size,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            ..self.dir_attr()
+        })
+    }
+
+    fn set_size(&self, _path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            if let (PULL_PROGRESS, Some(pull)) = (path, &self.pull) {
+                return Ok(pull.open_progress());
+            }
+
+            if let Some(rootfs_path) = Self::rootfs_path(path) {
+                let rootfs = self.rootfs(docker).await?;
+                return Ok(FileHandle::Disk(rootfs.open(rootfs_path)?));
+            }
+
+            let details = self.details(docker).await?;
+            let content = self.content(&details, path)?;
+
+            Ok(FileHandle::Snapshot(Arc::new(content.into_bytes())))
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        if path.starts_with(TAGS) {
+            return Ok(self.link());
+        }
+        if path == UNTAGGED_LINK {
+            return Ok(format!("../{}", self.link()));
+        }
+
+        let rootfs_path = Self::rootfs_path(path).ok_or(libc::EINVAL)?;
+
+        self.rootfs
+            .get()
+            .ok_or(libc::ENOENT)?
+            .get(rootfs_path)
+            .ok_or(libc::ENOENT)?
+            .link
+            .clone()
+            .ok_or(libc::EINVAL)
+    }
+}
+
+impl Image {
+    pub fn new(image: ImageSummary, parent_ino: u64, inodes: &Inodes, cache_dir: &Path) -> Self {
+        let name = digest(&image.id).to_string();
+        let tags = image
+            .repo_tags
+            .iter()
+            .filter(|tag| tag.as_str() != NONE_TAG)
+            .cloned()
+            .collect();
+
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Image, &image.id),
+            parent_ino,
+            cache_dir: cache_dir.join("images").join(&name),
+            name,
+            tags,
+            image,
+            details: Arc::new(OnceCell::new()),
+            rootfs: Arc::new(OnceCell::new()),
+            pull: None,
+            inodes: inodes.clone(),
+        }
+    }
+
+    // Images are rebuilt on every refresh, their details don't change with them.
+    pub fn keep_state(&mut self, previous: &Image) {
+        self.details = previous.details.clone();
+        self.rootfs = previous.rootfs.clone();
+        self.pull = previous.pull.clone();
+    }
+
+    // Exported filesystems take as much room as the image, they are dropped along with it.
+    pub fn remove_cache(&self) {
+        let dir = self.cache_dir.clone();
+        tokio::spawn(async move {
+            match tokio::fs::remove_dir_all(&dir).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    log::error!("Failed to remove cache {}: {}", dir.display(), e)
+                }
+                _ => (),
+            }
+        });
+    }
+
+    // Images pulled through the mount keep the progress of their pull.
+    pub fn set_pull(&mut self, pull: Arc<PullState>) {
+        self.pull = Some(pull);
+    }
+
+    pub fn get_tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    // Whether the reference names one of the tags, once both are normalized.
+    pub fn has_tag(&self, reference: &str) -> bool {
+        let reference = normalize_reference(reference);
+        self.tags
+            .iter()
+            .any(|tag| normalize_reference(tag) == reference)
+    }
+
+    // Target of the tag symlinks, relative to the images directory.
+    pub fn link(&self) -> String {
+        format!("{}/{}", BY_ID, self.name)
+    }
+
+    pub fn short_id(&self) -> &str {
+        &self.name[..SHORT_ID.min(self.name.len())]
+    }
+
+    // Link of the `untagged` group, for images without any tag.
+    pub fn untagged_attr(&self) -> Option<FileAttr> {
+        if !self.tags.is_empty() {
+            return None;
+        }
+
+        Some(FileAttr {
+            ino: self.entry_ino(UNTAGGED_LINK),
+            size: format!("../{}", self.link()).len() as u64,
+            kind: FileType::Symlink,
+            perm: 0o777,
+            nlink: 1,
+            ..self.dir_attr()
+        })
+    }
+
+    pub fn tag_attr(&self, tag: &str) -> Option<FileAttr> {
+        if !self.tags.iter().any(|image_tag| image_tag == tag) {
+            return None;
+        }
+
+        Some(FileAttr {
+            ino: self.entry_ino(&format!("{}{}", TAGS, tag)),
+            size: self.link().len() as u64,
+            kind: FileType::Symlink,
+            perm: 0o777,
+            nlink: 1,
+            ..self.dir_attr()
+        })
+    }
+
+    async fn details(&self, docker: &bollard::Docker) -> Result<Arc<ImageDetails>, libc::c_int> {
+        self.details
+            .get_or_try_init(|| async {
+                ImageDetails::load(docker, &self.image.id)
+                    .await
+                    .map(Arc::new)
+            })
+            .await
+            .cloned()
+    }
+
+    fn rootfs_path(path: &str) -> Option<&str> {
+        match path.split_once('/') {
+            Some((ROOTFS, rootfs_path)) => Some(rootfs_path),
+            None if path == ROOTFS => Some(""),
+            _ => None,
+        }
+    }
+
+    async fn rootfs(&self, docker: &bollard::Docker) -> Result<Arc<ImageRootFs>, libc::c_int> {
+        self.rootfs
+            .get_or_try_init(|| async {
+                ImageRootFs::load(docker, &self.image.id, self.cache_dir.clone())
+                    .await
+                    .map(Arc::new)
+            })
+            .await
+            .cloned()
+    }
+
+    async fn rootfs_entries(
+        &self,
+        path: &str,
+        rootfs_path: &str,
+        docker: &bollard::Docker,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let rootfs = self.rootfs(docker).await?;
+        let node = rootfs.get(rootfs_path).ok_or(libc::ENOENT)?;
+        if node.kind != FileType::Directory {
+            return Err(libc::ENOTDIR);
+        }
+
+        let parent_ino = match path.rsplit_once('/') {
+            Some((parent, _)) => self.entry_ino(parent),
+            None => self.ino,
+        };
+
+        let mut entries = vec![
+            DirectoryEntry::new(self.entry_ino(path), FileType::Directory, "."),
+            DirectoryEntry::new(parent_ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(node.children.iter().map(|(name, kind)| {
+            DirectoryEntry::new(self.entry_ino(&join_path(path, name)), *kind, name)
+        }));
+
+        Ok(entries)
+    }
+
+    fn is_file(&self, path: &str) -> bool {
+        match path.split_once('/') {
+            Some((LAYERS, _)) => true,
+            _ => ImageFile::try_from(path).is_ok(),
+        }
+    }
+
+    // Layers are files holding their size.
+    fn content(&self, details: &ImageDetails, path: &str) -> Result<String, libc::c_int> {
+        match path.split_once('/') {
+            Some((LAYERS, digest)) => details
+                .layers()
+                .into_iter()
+                .find(|(layer, _)| layer == digest)
+                .map(|(_, size)| match size {
+                    Some(size) => format!("{}\n", size),
+                    None => String::from("unknown\n"),
+                })
+                .ok_or(libc::ENOENT),
+            _ => ImageFile::try_from(path)
+                .map_err(|_| libc::ENOENT)?
+                .content(details),
+        }
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.ino, FileType::Directory, "."),
+            DirectoryEntry::new(self.parent_ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(ImageFile::iterator().map(|file| {
+            DirectoryEntry::new(
+                self.entry_ino(file.as_str()),
+                FileType::RegularFile,
+                file.as_str(),
+            )
+        }));
+
+        entries.extend(
+            [LAYERS, ROOTFS]
+                .iter()
+                .map(|name| DirectoryEntry::new(self.entry_ino(name), FileType::Directory, name)),
+        );
+
+        if self.pull.is_some() {
+            entries.push(DirectoryEntry::new(
+                self.entry_ino(PULL_PROGRESS),
+                FileType::RegularFile,
+                PULL_PROGRESS,
+            ));
+        }
+
+        entries
+    }
+
+    fn layer_entries(&self, details: &ImageDetails) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.entry_ino(LAYERS), FileType::Directory, "."),
+            DirectoryEntry::new(self.ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(details.layers().into_iter().map(|(digest, _)| {
+            DirectoryEntry::new(
+                self.entry_ino(&join_path(LAYERS, &digest)),
+                FileType::RegularFile,
+                &digest,
+            )
+        }));
+
+        entries
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Image, &self.image.id, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        let time = UNIX_EPOCH + Duration::from_secs(self.image.created.max(0) as u64);
+
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker_strategy::images::UntaggedImages;
+
+    #[test]
+    fn test_escape_name_and_back() {
+        let tag = "registry:5000/team/app%1:latest";
+        let escaped = escape_name(tag);
+
+        assert_eq!(escaped, "registry:5000%2Fteam%2Fapp%251:latest");
+        assert!(!escaped.contains('/'));
+        assert_eq!(unescape_name(&escaped), tag);
+    }
+
+    #[test]
+    fn test_unescape_name_keeps_escaped_percent() {
+        assert_eq!(unescape_name("app%252F:1"), "app%2F:1");
+    }
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(split_reference("nginx:latest"), Some(("nginx", "latest")));
+        assert_eq!(
+            split_reference("registry:5000/nginx:1.25"),
+            Some(("registry:5000/nginx", "1.25"))
+        );
+        assert_eq!(split_reference("registry:5000/nginx"), None);
+        assert_eq!(split_reference("nginx"), None);
+        assert_eq!(split_reference("nginx:"), None);
+        assert_eq!(split_reference(":latest"), None);
+    }
+
+    #[test]
+    fn test_normalize_reference() {
+        assert_eq!(
+            normalize_reference("docker.io/library/nginx:latest"),
+            "nginx:latest"
+        );
+        assert_eq!(normalize_reference("library/nginx:1"), "nginx:1");
+        assert_eq!(
+            normalize_reference("index.docker.io/team/app:1"),
+            "team/app:1"
+        );
+        assert_eq!(normalize_reference("nginx:latest"), "nginx:latest");
+        assert_eq!(
+            normalize_reference("quay.io/library/app:1"),
+            "quay.io/library/app:1"
+        );
+    }
+
+    fn image(id: &str, tags: &[&str], inodes: &Inodes) -> Image {
+        let summary = ImageSummary {
+            id: id.to_string(),
+            repo_tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+
+        Image::new(summary, 0, inodes, Path::new("/cache"))
+    }
+
+    #[test]
+    fn test_dangling_images_are_linked_from_untagged() {
+        let inodes = Inodes::default();
+        let id = format!("sha256:{}", "0123456789ab".repeat(5));
+        let dangling = image(&id, &[NONE_TAG], &inodes);
+        let tagged = image("sha256:fedcba987654", &["nginx:latest"], &inodes);
+
+        assert_eq!(dangling.short_id(), "0123456789ab");
+        let attr = dangling.untagged_attr().unwrap();
+        assert_eq!(attr.kind, FileType::Symlink);
+        assert_eq!(dangling.getattr(UNTAGGED_LINK).unwrap().ino, attr.ino);
+
+        let link = format!("../by-id/{}", digest(&id));
+        assert_eq!(dangling.readlink(UNTAGGED_LINK).unwrap(), link);
+        assert_eq!(attr.size, link.len() as u64);
+
+        assert!(tagged.untagged_attr().is_none());
+        assert_eq!(tagged.getattr(UNTAGGED_LINK).err(), Some(libc::ENOENT));
+
+        let mut untagged = UntaggedImages::new(&inodes);
+        untagged.add(&dangling);
+        untagged.add(&tagged);
+
+        let entries = futures::executor::block_on(untagged.read_dir("", &docker())).unwrap();
+        let entries: Vec<(&str, FileType, u64)> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.kind, entry.ino))
+            .collect();
+        assert_eq!(
+            entries[2..],
+            [("0123456789ab", FileType::Symlink, attr.ino)]
+        );
+        assert_eq!(
+            futures::executor::block_on(untagged.lookup("", "0123456789ab", &docker()))
+                .unwrap()
+                .ino,
+            attr.ino
+        );
+    }
+
+    fn docker() -> bollard::Docker {
+        bollard::Docker::connect_with_http("http://127.0.0.1:1", 1, bollard::API_DEFAULT_VERSION)
+            .unwrap()
+    }
+}
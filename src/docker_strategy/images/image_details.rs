@@ -0,0 +1,123 @@
+use bollard::service::{HistoryResponseItem, ImageInspect};
+
+use crate::docker_strategy::docker::errno_from_docker_error;
+
+// Details of an image which aren't part of its summary. Images are immutable, they are fetched
+// once and kept for as long as the image is listed.
+#[derive(Debug)]
+pub struct ImageDetails {
+    pub inspect: ImageInspect,
+    pub history: Vec<HistoryResponseItem>,
+}
+
+impl ImageDetails {
+    pub async fn load(docker: &bollard::Docker, reference: &str) -> Result<Self, libc::c_int> {
+        let inspect = docker.inspect_image(reference).await.map_err(|e| {
+            log::error!("Failed to inspect image {}: {}", reference, e);
+            errno_from_docker_error(&e)
+        })?;
+
+        let history = docker.image_history(reference).await.map_err(|e| {
+            log::error!("Failed to fetch history of image {}: {}", reference, e);
+            errno_from_docker_error(&e)
+        })?;
+
+        Ok(Self { inspect, history })
+    }
+
+    // Layer digests from the bottom one, with their size when it can be told from the history.
+    // The history lists every build step newest first, steps which didn't add a layer having
+    // no size: sizes are only reported when the steps left match the layers one to one.
+    pub fn layers(&self) -> Vec<(String, Option<i64>)> {
+        let layers = self
+            .inspect
+            .root_fs
+            .as_ref()
+            .and_then(|root_fs| root_fs.layers.clone())
+            .unwrap_or_default();
+
+        let sizes: Vec<i64> = self
+            .history
+            .iter()
+            .rev()
+            .map(|step| step.size)
+            .filter(|size| *size > 0)
+            .collect();
+
+        if sizes.len() == layers.len() {
+            layers
+                .into_iter()
+                .zip(sizes.into_iter().map(Some))
+                .collect()
+        } else {
+            layers.into_iter().map(|layer| (layer, None)).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn details(layers: &[&str], sizes: &[i64]) -> ImageDetails {
+        let history = sizes
+            .iter()
+            .map(|size| {
+                serde_json::from_value(serde_json::json!({
+                    "Id": "<missing>",
+                    "Created": 0,
+                    "CreatedBy": "",
+                    "Tags": [],
+                    "Size": size,
+                    "Comment": "",
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        ImageDetails {
+            inspect: serde_json::from_value(serde_json::json!({
+                "RootFS": { "Type": "layers", "Layers": layers },
+            }))
+            .unwrap(),
+            history,
+        }
+    }
+
+    #[test]
+    fn test_layers_with_sizes_of_the_history() {
+        // Newest step first, the empty ones adding no layer.
+        let details = details(&["sha256:a", "sha256:b"], &[0, 20, 0, 10]);
+
+        assert_eq!(
+            details.layers(),
+            vec![
+                (String::from("sha256:a"), Some(10)),
+                (String::from("sha256:b"), Some(20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers_without_matching_history() {
+        let details = details(&["sha256:a", "sha256:b"], &[20]);
+
+        assert_eq!(
+            details.layers(),
+            vec![
+                (String::from("sha256:a"), None),
+                (String::from("sha256:b"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers_of_image_without_root_fs() {
+        let details = ImageDetails {
+            inspect: ImageInspect::default(),
+            history: Vec::new(),
+        };
+
+        assert!(details.layers().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::docker_strategy::{docker_strategy::DockerError, images::image_details::ImageDetails};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImageFile {
+    Inspect,
+    History,
+    Env,
+    Entrypoint,
+    Cmd,
+    ExposedPorts,
+    Labels,
+}
+
+impl TryFrom<&str> for ImageFile {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "inspect.json" => Ok(ImageFile::Inspect),
+            "history" => Ok(ImageFile::History),
+            "env" => Ok(ImageFile::Env),
+            "entrypoint" => Ok(ImageFile::Entrypoint),
+            "cmd" => Ok(ImageFile::Cmd),
+            "exposed_ports" => Ok(ImageFile::ExposedPorts),
+            "labels" => Ok(ImageFile::Labels),
+            _ => Err(DockerError::UnknownChildEntry),
+        }
+    }
+}
+
+impl ImageFile {
+    pub fn iterator() -> impl Iterator<Item = ImageFile> {
+        [
+            ImageFile::Inspect,
+            ImageFile::History,
+            ImageFile::Env,
+            ImageFile::Entrypoint,
+            ImageFile::Cmd,
+            ImageFile::ExposedPorts,
+            ImageFile::Labels,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFile::Inspect => "inspect.json",
+            ImageFile::History => "history",
+            ImageFile::Env => "env",
+            ImageFile::Entrypoint => "entrypoint",
+            ImageFile::Cmd => "cmd",
+            ImageFile::ExposedPorts => "exposed_ports",
+            ImageFile::Labels => "labels",
+        }
+    }
+
+    // Lists are written one item per line, every file ending with a newline like container
+    // files do.
+    pub fn content(&self, details: &ImageDetails) -> Result<String, libc::c_int> {
+        let config = details.inspect.config.clone().unwrap_or_default();
+
+        let mut lines = match self {
+            ImageFile::Inspect => {
+                return serde_json::to_string_pretty(&details.inspect)
+                    .map(|inspect| inspect + "\n")
+                    .map_err(|e| {
+                        log::error!("Failed to serialize image: {}", e);
+                        libc::EIO
+                    })
+            }
+            ImageFile::History => details
+                .history
+                .iter()
+                .map(|step| {
+                    format!(
+                        "{}\t{}\t{}\t{}",
+                        step.id, step.created, step.size, step.created_by
+                    )
+                })
+                .collect(),
+            ImageFile::Env => config.env.unwrap_or_default(),
+            ImageFile::Entrypoint => config.entrypoint.unwrap_or_default(),
+            ImageFile::Cmd => config.cmd.unwrap_or_default(),
+            ImageFile::ExposedPorts => {
+                let mut ports: Vec<String> = config
+                    .exposed_ports
+                    .unwrap_or_default()
+                    .into_keys()
+                    .collect();
+                ports.sort();
+                ports
+            }
+            ImageFile::Labels => {
+                let mut labels: Vec<String> = config
+                    .labels
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
+                labels.sort();
+                labels
+            }
+        };
+
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use bollard::image::CreateImageOptions;
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use tokio::sync::watch;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        containers::{LogStream, LogWriter},
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        images::image::split_reference,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+pub const PULL_PROGRESS: &str = ".pull_progress";
+const PULL_ERROR: &str = ".pull_error";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullStatus {
+    Pulling,
+    Pulled,
+    Failed { errno: libc::c_int, message: String },
+}
+
+// Pull of an image running in the background, its progress events being kept as JSON lines
+// for as long as the pull is known to the mount.
+#[derive(Debug)]
+pub struct PullState {
+    progress: Arc<LogStream>,
+    status: watch::Receiver<PullStatus>,
+}
+
+impl PullState {
+    pub fn start(docker: &bollard::Docker, reference: &str) -> Arc<PullState> {
+        let (sender, status) = watch::channel(PullStatus::Pulling);
+        let docker = docker.clone();
+        let reference = reference.to_string();
+
+        let progress = LogStream::spawn(|writer| async move {
+            let status = match Self::pull(&docker, &reference, &writer).await {
+                Ok(()) => PullStatus::Pulled,
+                Err(e) => {
+                    log::error!("Failed to pull image {}: {}", reference, e);
+                    let message = match &e {
+                        bollard::errors::Error::DockerStreamError { error } => error.clone(),
+                        bollard::errors::Error::DockerResponseServerError { message, .. } => {
+                            message.clone()
+                        }
+                        _ => e.to_string(),
+                    };
+
+                    PullStatus::Failed {
+                        errno: errno_from_docker_error(&e),
+                        message,
+                    }
+                }
+            };
+            sender.send_replace(status);
+        });
+
+        Arc::new(Self {
+            progress: Arc::new(progress),
+            status,
+        })
+    }
+
+    async fn pull(
+        docker: &bollard::Docker,
+        reference: &str,
+        writer: &LogWriter,
+    ) -> Result<(), bollard::errors::Error> {
+        let (from_image, tag) = split_reference(reference).unwrap_or((reference, "latest"));
+
+        let mut events = docker.create_image(
+            Some(CreateImageOptions {
+                from_image,
+                tag,
+                ..Default::default()
+            }),
+            None,
+            None,
+        );
+
+        while let Some(event) = events.next().await {
+            let event = event?;
+            if let Some(error) = event.error {
+                return Err(bollard::errors::Error::DockerStreamError { error });
+            }
+
+            if let Ok(line) = serde_json::to_string(&event) {
+                writer.push(format!("{}\n", line).as_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn status(&self) -> PullStatus {
+        self.status.borrow().clone()
+    }
+
+    // A pull still running once its task is gone was cancelled.
+    pub async fn wait(&self) -> PullStatus {
+        let mut status = self.status.clone();
+        while *status.borrow() == PullStatus::Pulling {
+            if status.changed().await.is_err() {
+                break;
+            }
+        }
+
+        let status = status.borrow().clone();
+        match status {
+            PullStatus::Pulling => PullStatus::Failed {
+                errno: libc::ECANCELED,
+                message: String::from("Pull cancelled"),
+            },
+            status => status,
+        }
+    }
+
+    // Stops the pull, whoever else waits for it.
+    pub fn cancel(&self) {
+        self.progress.stop();
+    }
+
+    pub fn progress_attr(&self, attr: FileAttr) -> FileAttr {
+        FileAttr {
+            size: self.progress.len(),
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            ..attr
+        }
+    }
+
+    pub fn open_progress(&self) -> FileHandle {
+        FileHandle::Logs {
+            stream: self.progress.clone(),
+            last_eof: Mutex::new(None),
+        }
+    }
+}
+
+// Placeholder created by `mkdir` in the images directory, until the image has been pulled. A
+// failed pull is kept along with its error until the directory is removed.
+#[derive(Debug)]
+pub struct PullingImage {
+    ino: u64,
+    name: String,
+    created: SystemTime,
+    state: Arc<PullState>,
+    inodes: Inodes,
+}
+
+impl From<PullingImage> for Box<dyn ChildDirectory> {
+    fn from(value: PullingImage) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for PullingImage {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Images
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if !path.is_empty() {
+                return Err(libc::ENOTDIR);
+            }
+
+            let mut entries = vec![
+                DirectoryEntry::new(self.ino, FileType::Directory, "."),
+                DirectoryEntry::new(ParentDirectories::Images as u64, FileType::Directory, ".."),
+                DirectoryEntry::new(
+                    self.entry_ino(PULL_PROGRESS),
+                    FileType::RegularFile,
+                    PULL_PROGRESS,
+                ),
+            ];
+
+            if let PullStatus::Failed { .. } = self.state.status() {
+                entries.push(DirectoryEntry::new(
+                    self.entry_ino(PULL_ERROR),
+                    FileType::RegularFile,
+                    PULL_ERROR,
+                ));
+            }
+
+            Ok(entries)
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move { self.getattr(&join_path(path, name)) }.boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match path {
+            "" => Ok(self.dir_attr()),
+            PULL_PROGRESS => Ok(self.state.progress_attr(FileAttr {
+                ino: self.entry_ino(PULL_PROGRESS),
+                ..self.dir_attr()
+            })),
+            PULL_ERROR => Ok(FileAttr {
+                ino: self.entry_ino(PULL_ERROR),
+                size: self.error().ok_or(libc::ENOENT)?.len() as u64,
+                kind: FileType::RegularFile,
+                perm: 0o444,
+                nlink: 1,
+                ..self.dir_attr()
+            }),
+            _ => Err(libc::ENOENT),
+        }
+    }
+
+    fn set_size(&self, _path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            match path {
+                PULL_PROGRESS => Ok(self.state.open_progress()),
+                PULL_ERROR => Ok(FileHandle::Snapshot(Arc::new(
+                    self.error().ok_or(libc::ENOENT)?.into_bytes(),
+                ))),
+                _ => Err(libc::ENOENT),
+            }
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, _path: &str) -> Result<String, libc::c_int> {
+        Err(libc::EINVAL)
+    }
+}
+
+impl PullingImage {
+    pub fn new(name: &str, state: Arc<PullState>, inodes: &Inodes) -> Self {
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::PullingImage, name),
+            name: name.to_string(),
+            created: SystemTime::now(),
+            state,
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn get_state(&self) -> &Arc<PullState> {
+        &self.state
+    }
+
+    pub fn is_pulled(&self) -> bool {
+        self.state.status() == PullStatus::Pulled
+    }
+
+    fn error(&self) -> Option<String> {
+        match self.state.status() {
+            PullStatus::Failed { message, .. } => Some(format!("{}\n", message)),
+            _ => None,
+        }
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::PullingImage, &self.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use bollard::container::{Config, CreateContainerOptions, RemoveContainerOptions};
+
+use crate::docker_strategy::{
+    containers::{container_rootfs::RootFsNode, RootFs},
+    docker::{errno_from_docker_error, errno_from_io_error},
+};
+
+const ARCHIVE: &str = "rootfs.tar";
+const FILES: &str = "files";
+
+// Filesystem of an image, exported once from a container created but never started and kept
+// on disk in the cache directory of the image. Files are extracted from the archive the first
+// time they are opened.
+#[derive(Debug)]
+pub struct ImageRootFs {
+    dir: PathBuf,
+    tree: RootFs,
+}
+
+impl ImageRootFs {
+    pub async fn load(
+        docker: &bollard::Docker,
+        image: &str,
+        dir: PathBuf,
+    ) -> Result<ImageRootFs, libc::c_int> {
+        let archive = dir.join(ARCHIVE);
+        if !archive.exists() {
+            Self::export(docker, image, &dir).await?;
+        }
+
+        Ok(Self {
+            tree: RootFs::load_archive(archive, "").await?,
+            dir,
+        })
+    }
+
+    async fn export(docker: &bollard::Docker, image: &str, dir: &Path) -> Result<(), libc::c_int> {
+        // Images without a command can't be created as is, it is never run anyway.
+        let container = docker
+            .create_container(
+                None::<CreateContainerOptions<String>>,
+                Config {
+                    image: Some(image.to_string()),
+                    entrypoint: Some(vec![String::from("/")]),
+                    network_disabled: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to create container from image {}: {}", image, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        let result = RootFs::download(docker, &container.id, "/", &dir.join(ARCHIVE)).await;
+
+        if let Err(e) = docker
+            .remove_container(
+                &container.id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+        {
+            log::error!("Failed to remove container {}: {}", container.id, e);
+        }
+
+        result
+    }
+
+    pub fn get(&self, path: &str) -> Option<&RootFsNode> {
+        self.tree.get(path)
+    }
+
+    pub fn open(&self, path: &str) -> Result<File, libc::c_int> {
+        let target = self.dir.join(FILES).join(path);
+
+        if !target.exists() {
+            self.tree
+                .extract(&self.dir.join(ARCHIVE), path, &target)
+                .map_err(|e| {
+                    log::error!("Failed to extract {}: {}", path, e);
+                    errno_from_io_error(e)
+                })?;
+        }
+
+        File::open(target).map_err(errno_from_io_error)
+    }
+}
@@ -0,0 +1,330 @@
+use bollard::image::{RemoveImageOptions, TagImageOptions};
+use fuser::{FileAttr, FileType};
+use std::{path::Path, sync::Arc, time::UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        docker::errno_from_docker_error,
+        images::{
+            image::{escape_name, split_reference, unescape_name},
+            images_by_id::BY_ID,
+            untagged_images::UNTAGGED,
+            Image, ImagesById, PullState, PullStatus, PullingImage, UntaggedImages,
+        },
+        parent_directories::ParentDirectories,
+        Docker,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+// Image carrying a tag, given as the escaped name of its symlink or as any other reference the
+// daemon normalizes to it.
+fn find_tag<'a>(docker: &'a Docker, name: &str) -> Option<&'a Image> {
+    let tag = unescape_name(name);
+
+    docker
+        .get::<Image>()
+        .into_iter()
+        .find(|image| image.has_tag(&tag))
+}
+
+impl ParentDirectories {
+    pub(crate) async fn images_root_lookup(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_images().await {
+            log::error!("Failed to update images: {}", e);
+        }
+
+        let image_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let tag = find_tag(&docker, image_name)
+            .and_then(|image| image.tag_attr(&unescape_name(image_name)));
+        let attr = match tag {
+            Some(attr) => attr,
+            None => {
+                docker
+                    .get::<ImagesById>()
+                    .into_iter()
+                    .map(|by_id| (by_id.get_name(), by_id.dir_attr()))
+                    .chain(
+                        docker
+                            .get::<UntaggedImages>()
+                            .into_iter()
+                            .map(|untagged| (untagged.get_name(), untagged.dir_attr())),
+                    )
+                    .chain(
+                        docker
+                            .get::<PullingImage>()
+                            .into_iter()
+                            .map(|pull| (pull.get_name(), pull.dir_attr())),
+                    )
+                    .find(|(name, _)| name.as_str() == image_name)
+                    .ok_or(libc::ENOENT)?
+                    .1
+            }
+        };
+        docker.remember(attr.ino);
+
+        Ok(attr)
+    }
+
+    // Pulls `repo:tag`, waiting for the pull to finish unless pulls are asynchronous.
+    pub(crate) async fn images_root_mkdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let image_name = name.to_str().ok_or(libc::EINVAL)?;
+        let reference = unescape_name(image_name);
+        if split_reference(&reference).is_none() {
+            return Err(libc::EINVAL);
+        }
+
+        let (attr, state) = {
+            let mut docker = docker.lock().await;
+            if let Err(e) = docker.update_images().await {
+                log::error!("Failed to update images: {}", e);
+            }
+
+            let exists = find_tag(&docker, image_name).is_some()
+                || docker
+                    .get::<PullingImage>()
+                    .into_iter()
+                    .any(|pull| pull.get_name() == image_name);
+            if exists {
+                return Err(libc::EEXIST);
+            }
+
+            let state = PullState::start(docker.get_docker(), &reference);
+            let pull = PullingImage::new(image_name, state.clone(), docker.get_inodes());
+            let attr = pull.dir_attr();
+            docker.insert_child(pull.into());
+
+            if docker.get_options().pull_async {
+                return Ok(attr);
+            }
+
+            (attr, state)
+        };
+
+        // The lock isn't held meanwhile, the placeholder being browsable during the pull.
+        let status = state.wait().await;
+
+        let mut docker = docker.lock().await;
+        match status {
+            PullStatus::Failed { errno, .. } => {
+                docker.remove_child(attr.ino);
+                Err(errno)
+            }
+            // The tag is a symlink to the image, but the kernel only accepts a directory from
+            // `mkdir`. The placeholder is answered rather than the image directory, which would
+            // alias it, its entry being replaced by the symlink once it expires.
+            _ => {
+                if let Err(e) = docker.update_images().await {
+                    log::error!("Failed to update images: {}", e);
+                }
+
+                find_tag(&docker, image_name).ok_or(libc::ENOENT)?;
+                Ok(attr)
+            }
+        }
+    }
+
+    // Tags the image the link points to, given as an id, a path to its directory or a tag.
+    pub(crate) async fn images_root_symlink(
+        name: &std::ffi::OsStr,
+        link: &Path,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let image_name = name.to_str().ok_or(libc::EINVAL)?;
+        let tag = unescape_name(image_name);
+        let (repo, image_tag) = split_reference(&tag).ok_or(libc::EINVAL)?;
+        let source = link
+            .file_name()
+            .and_then(|source| source.to_str())
+            .map(unescape_name)
+            .ok_or(libc::EINVAL)?;
+
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_images().await {
+            log::error!("Failed to update images: {}", e);
+        }
+
+        if find_tag(&docker, image_name).is_some() {
+            return Err(libc::EEXIST);
+        }
+
+        docker
+            .get_docker()
+            .tag_image(
+                &source,
+                Some(TagImageOptions {
+                    repo,
+                    tag: image_tag,
+                }),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to tag image {} as {}: {}", source, tag, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        docker.force_update_images().await.map_err(|e| {
+            log::error!("Failed to update images: {}", e);
+            libc::EIO
+        })?;
+
+        find_tag(&docker, image_name)
+            .and_then(|image| image.tag_attr(&tag))
+            .ok_or(libc::ENOENT)
+    }
+
+    // Removes a tag only, the image being deleted along with its last tag.
+    pub(crate) async fn images_root_unlink(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        let image_name = name.to_str().ok_or(libc::ENOENT)?;
+        let tag = unescape_name(image_name);
+
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_images().await {
+            log::error!("Failed to update images: {}", e);
+        }
+
+        if find_tag(&docker, image_name).is_none() {
+            let is_directory = image_name == BY_ID
+                || image_name == UNTAGGED
+                || docker
+                    .get::<PullingImage>()
+                    .into_iter()
+                    .any(|pull| pull.get_name() == image_name);
+
+            return Err(if is_directory {
+                libc::EISDIR
+            } else {
+                libc::ENOENT
+            });
+        }
+
+        docker
+            .get_docker()
+            .remove_image(
+                &tag,
+                Some(RemoveImageOptions {
+                    force: false,
+                    noprune: true,
+                }),
+                None,
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to untag image {}: {}", tag, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        if let Err(e) = docker.force_update_images().await {
+            log::error!("Failed to update images: {}", e);
+        }
+        Ok(())
+    }
+
+    // Only pulls can be removed, stopping them if still running.
+    pub(crate) async fn images_root_rmdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        let mut docker = docker.lock().await;
+        let image_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let pull = docker
+            .get::<PullingImage>()
+            .into_iter()
+            .find(|pull| pull.get_name() == image_name)
+            .ok_or(libc::EPERM)?;
+        pull.get_state().cancel();
+
+        let ino = pull.get_ino();
+        docker.remove_child(ino);
+        Ok(())
+    }
+
+    pub(crate) fn images_root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.into(),
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH, // 1970-01-01 00:00:00
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o777,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+
+    pub(crate) async fn images_root_read_dir(
+        &self,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let mut entries = vec![
+            DirectoryEntry::new(ParentDirectories::Images as u64, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
+        ];
+
+        let mut docker = docker.lock().await;
+
+        if let Err(error) = docker.update_images().await {
+            log::debug!("Failed to update images, error: {}", error);
+        }
+
+        docker.get::<Image>().into_iter().for_each(|image| {
+            entries.extend(image.get_tags().iter().filter_map(|tag| {
+                image
+                    .tag_attr(tag)
+                    .map(|attr| DirectoryEntry::new(attr.ino, FileType::Symlink, &escape_name(tag)))
+            }))
+        });
+
+        docker.get::<PullingImage>().into_iter().for_each(|pull| {
+            entries.push(DirectoryEntry::new(
+                pull.get_ino(),
+                FileType::Directory,
+                pull.get_name(),
+            ))
+        });
+
+        docker.get::<ImagesById>().into_iter().for_each(|by_id| {
+            entries.push(DirectoryEntry::new(
+                by_id.get_ino(),
+                FileType::Directory,
+                by_id.get_name(),
+            ))
+        });
+
+        docker
+            .get::<UntaggedImages>()
+            .into_iter()
+            .for_each(|untagged| {
+                entries.push(DirectoryEntry::new(
+                    untagged.get_ino(),
+                    FileType::Directory,
+                    untagged.get_name(),
+                ))
+            });
+
+        entries[2..].sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,16 @@
+pub(crate) mod image;
+pub(crate) mod image_details;
+pub(crate) mod image_file;
+pub(crate) mod image_pull;
+pub(crate) mod image_rootfs;
+pub(crate) mod images_by_id;
+pub(crate) mod images_root;
+pub(crate) mod untagged_images;
+
+pub(crate) use image::Image;
+pub(crate) use image_details::ImageDetails;
+pub(crate) use image_file::ImageFile;
+pub(crate) use image_pull::{PullState, PullStatus, PullingImage};
+pub(crate) use image_rootfs::ImageRootFs;
+pub(crate) use images_by_id::ImagesById;
+pub(crate) use untagged_images::UntaggedImages;
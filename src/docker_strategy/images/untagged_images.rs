@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, time::UNIX_EPOCH};
+
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        file_handle::FileHandle,
+        images::Image,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+pub const UNTAGGED: &str = "untagged";
+
+// Group of the dangling images, listed by their short id as symlinks to their directory in the
+// `by-id` group. The links belong to the images, which resolve them.
+#[derive(Debug)]
+pub struct UntaggedImages {
+    ino: u64,
+    name: String,
+    images: BTreeMap<String, FileAttr>,
+}
+
+impl From<UntaggedImages> for Box<dyn ChildDirectory> {
+    fn from(value: UntaggedImages) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for UntaggedImages {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Images
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if !path.is_empty() {
+                return Err(libc::ENOTDIR);
+            }
+
+            let mut entries = vec![
+                DirectoryEntry::new(self.ino, FileType::Directory, "."),
+                DirectoryEntry::new(ParentDirectories::Images as u64, FileType::Directory, ".."),
+            ];
+
+            entries.extend(
+                self.images
+                    .iter()
+                    .map(|(name, attr)| DirectoryEntry::new(attr.ino, FileType::Symlink, name)),
+            );
+
+            Ok(entries)
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            if !path.is_empty() {
+                return Err(libc::ENOTDIR);
+            }
+
+            self.images.get(name).copied().ok_or(libc::ENOENT)
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match path {
+            "" => Ok(self.dir_attr()),
+            _ => Err(libc::ENOENT),
+        }
+    }
+
+    fn set_size(&self, _path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn open<'a>(
+        &'a self,
+        _path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move { Err(libc::ENOENT) }.boxed()
+    }
+
+    fn readlink(&self, _path: &str) -> Result<String, libc::c_int> {
+        Err(libc::EINVAL)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        match self.images.contains_key(path) {
+            true => Err(libc::EPERM),
+            false => Err(libc::ENOENT),
+        }
+    }
+}
+
+impl UntaggedImages {
+    pub fn new(inodes: &Inodes) -> Self {
+        Self {
+            ino: inodes
+                .lock()
+                .unwrap()
+                .pin(InodeKind::UntaggedImages, UNTAGGED),
+            name: UNTAGGED.to_string(),
+            images: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, image: &Image) {
+        if let Some(attr) = image.untagged_attr() {
+            self.images.insert(image.short_id().to_string(), attr);
+        }
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
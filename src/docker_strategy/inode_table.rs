@@ -0,0 +1,329 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::docker_strategy::parent_directories::ParentDirectories;
+
+// Inodes up to this one are the fixed inodes of `ParentDirectories`.
+const FIRST_INODE: u64 = ParentDirectories::Pods as u64 + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InodeKind {
+    Container,
+    DraftContainer,
+    Image,
+    PullingImage,
+    ImagesById,
+    UntaggedImages,
+    Volume,
+    Network,
+    Pod,
+}
+
+// Identifies a file of the mount: the docker object owning it and its path inside of the
+// object directory, the empty path being the directory itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InodeKey {
+    pub kind: InodeKind,
+    pub id: String,
+    pub path: String,
+}
+
+impl InodeKey {
+    pub fn new(kind: InodeKind, id: &str, path: &str) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    pub fn object(&self) -> InodeKey {
+        InodeKey::new(self.kind, &self.id, "")
+    }
+}
+
+#[derive(Debug)]
+struct InodeEntry {
+    key: InodeKey,
+    lookups: u64,
+    pinned: bool,
+}
+
+// Inodes are allocated in increasing order and never reused. An inode is dropped once the
+// kernel forgot every lookup of it and no directory object holds it, a later lookup of the same
+// file allocating a new one.
+#[derive(Debug)]
+pub struct InodeTable {
+    inodes: HashMap<InodeKey, u64>,
+    entries: HashMap<u64, InodeEntry>,
+    next_inode: u64,
+}
+
+pub type Inodes = Arc<Mutex<InodeTable>>;
+
+impl Default for InodeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InodeTable {
+    pub fn new() -> Self {
+        Self {
+            inodes: HashMap::new(),
+            entries: HashMap::new(),
+            next_inode: FIRST_INODE,
+        }
+    }
+
+    pub fn get_or_allocate(&mut self, kind: InodeKind, id: &str, path: &str) -> u64 {
+        let key = InodeKey::new(kind, id, path);
+        if let Some(inode) = self.inodes.get(&key) {
+            return *inode;
+        }
+
+        let inode = self.next_inode;
+        self.next_inode += 1;
+
+        self.inodes.insert(key.clone(), inode);
+        self.entries.insert(
+            inode,
+            InodeEntry {
+                key,
+                lookups: 0,
+                pinned: false,
+            },
+        );
+
+        inode
+    }
+
+    // Directory objects hold their own inode for as long as they are known to the mount.
+    pub fn pin(&mut self, kind: InodeKind, id: &str) -> u64 {
+        let inode = self.get_or_allocate(kind, id, "");
+        if let Some(entry) = self.entries.get_mut(&inode) {
+            entry.pinned = true;
+        }
+        inode
+    }
+
+    // Releases the inode of a directory object, and every inode inside of it the kernel
+    // doesn't know about.
+    pub fn unpin(&mut self, inode: u64) {
+        let object = match self.entries.get_mut(&inode) {
+            Some(entry) => {
+                entry.pinned = false;
+                entry.key.object()
+            }
+            None => return,
+        };
+
+        let released: Vec<u64> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.lookups == 0
+                    && !entry.pinned
+                    && entry.key.kind == object.kind
+                    && entry.key.id == object.id
+            })
+            .map(|(inode, _)| *inode)
+            .collect();
+
+        released.iter().for_each(|inode| self.release(*inode));
+    }
+
+    pub fn get(&self, inode: u64) -> Option<&InodeKey> {
+        self.entries.get(&inode).map(|entry| &entry.key)
+    }
+
+    pub fn find(&self, key: &InodeKey) -> Option<u64> {
+        self.inodes.get(key).copied()
+    }
+
+    // Counts an inode handed to the kernel by a `lookup`, `mkdir` or `create` reply.
+    pub fn remember(&mut self, inode: u64) {
+        if let Some(entry) = self.entries.get_mut(&inode) {
+            entry.lookups += 1;
+        }
+    }
+
+    pub fn forget(&mut self, inode: u64, lookups: u64) {
+        let entry = match self.entries.get_mut(&inode) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        entry.lookups = entry.lookups.saturating_sub(lookups);
+        if entry.lookups == 0 && !entry.pinned {
+            self.release(inode);
+        }
+    }
+
+    // Moves the inodes of a renamed file and of everything inside of it to their new paths, so
+    // that the kernel keeps its inodes. An inode replaced at the new path is no longer found by
+    // path, but stays until the kernel forgets it.
+    pub fn rename(&mut self, kind: InodeKind, id: &str, from: &str, to: &str) {
+        let prefix = format!("{}/", from);
+        let moved: Vec<u64> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.key.kind == kind
+                    && entry.key.id == id
+                    && (entry.key.path == from || entry.key.path.starts_with(&prefix))
+            })
+            .map(|(inode, _)| *inode)
+            .collect();
+
+        for inode in moved {
+            let key = match self.entries.get_mut(&inode) {
+                Some(entry) => {
+                    self.inodes.remove(&entry.key);
+                    entry.key.path = format!("{}{}", to, &entry.key.path[from.len()..]);
+                    entry.key.clone()
+                }
+                None => continue,
+            };
+
+            if let Some(replaced) = self.inodes.insert(key, inode) {
+                match self.entries.get(&replaced) {
+                    Some(entry) if entry.lookups == 0 && !entry.pinned => {
+                        self.entries.remove(&replaced);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // The path of a replaced inode may belong to another one since.
+    fn release(&mut self, inode: u64) {
+        if let Some(entry) = self.entries.remove(&inode) {
+            if self.inodes.get(&entry.key) == Some(&inode) {
+                self.inodes.remove(&entry.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_or_allocate_is_stable() {
+        let mut inodes = InodeTable::new();
+
+        let file = inodes.get_or_allocate(InodeKind::Container, "abc", "logs");
+        let other = inodes.get_or_allocate(InodeKind::Container, "abc", "status");
+
+        assert!(file >= FIRST_INODE);
+        assert_ne!(file, other);
+        assert_eq!(
+            file,
+            inodes.get_or_allocate(InodeKind::Container, "abc", "logs")
+        );
+        assert_eq!(
+            inodes.get(file),
+            Some(&InodeKey::new(InodeKind::Container, "abc", "logs"))
+        );
+    }
+
+    #[test]
+    fn test_forget_releases_inode() {
+        let mut inodes = InodeTable::new();
+        let file = inodes.get_or_allocate(InodeKind::Volume, "data", "file");
+
+        inodes.remember(file);
+        inodes.remember(file);
+        inodes.forget(file, 1);
+        assert!(inodes.get(file).is_some());
+
+        inodes.forget(file, 1);
+        assert!(inodes.get(file).is_none());
+
+        // Inodes are never reused.
+        let again = inodes.get_or_allocate(InodeKind::Volume, "data", "file");
+        assert!(again > file);
+    }
+
+    #[test]
+    fn test_pinned_inode_survives_forget() {
+        let mut inodes = InodeTable::new();
+        let dir = inodes.pin(InodeKind::Image, "sha256:1");
+
+        inodes.remember(dir);
+        inodes.forget(dir, 1);
+        assert!(inodes.get(dir).is_some());
+        assert_eq!(inodes.pin(InodeKind::Image, "sha256:1"), dir);
+    }
+
+    #[test]
+    fn test_unpin_releases_forgotten_inodes_of_the_object() {
+        let mut inodes = InodeTable::new();
+        let dir = inodes.pin(InodeKind::Container, "abc");
+        let forgotten = inodes.get_or_allocate(InodeKind::Container, "abc", "status");
+        let known = inodes.get_or_allocate(InodeKind::Container, "abc", "logs");
+        let other = inodes.get_or_allocate(InodeKind::Container, "def", "status");
+        inodes.remember(known);
+
+        inodes.unpin(dir);
+
+        assert!(inodes.get(dir).is_none());
+        assert!(inodes.get(forgotten).is_none());
+        assert!(inodes.get(known).is_some());
+        assert!(inodes.get(other).is_some());
+
+        inodes.forget(known, 1);
+        assert!(inodes.get(known).is_none());
+    }
+
+    #[test]
+    fn test_rename_moves_inodes_inside_of_the_path() {
+        let mut inodes = InodeTable::new();
+        let dir = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/a");
+        let file = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/a/file");
+        let sibling = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/ab");
+
+        inodes.rename(InodeKind::Volume, "data", "_data/a", "_data/b");
+
+        let key = |path: &str| InodeKey::new(InodeKind::Volume, "data", path);
+        assert_eq!(inodes.find(&key("_data/b")), Some(dir));
+        assert_eq!(inodes.find(&key("_data/b/file")), Some(file));
+        assert_eq!(inodes.find(&key("_data/ab")), Some(sibling));
+        assert_eq!(inodes.find(&key("_data/a")), None);
+    }
+
+    #[test]
+    fn test_rename_keeps_replaced_inode_until_forgotten() {
+        let mut inodes = InodeTable::new();
+        let moved = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/new");
+        let replaced = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/old");
+        inodes.remember(replaced);
+
+        inodes.rename(InodeKind::Volume, "data", "_data/new", "_data/old");
+
+        let key = InodeKey::new(InodeKind::Volume, "data", "_data/old");
+        assert_eq!(inodes.find(&key), Some(moved));
+        assert!(inodes.get(replaced).is_some());
+
+        // Forgetting the replaced inode leaves the path to the moved one.
+        inodes.forget(replaced, 1);
+        assert!(inodes.get(replaced).is_none());
+        assert_eq!(inodes.find(&key), Some(moved));
+    }
+
+    #[test]
+    fn test_rename_drops_unknown_replaced_inode() {
+        let mut inodes = InodeTable::new();
+        inodes.get_or_allocate(InodeKind::Volume, "data", "_data/new");
+        let replaced = inodes.get_or_allocate(InodeKind::Volume, "data", "_data/old");
+
+        inodes.rename(InodeKind::Volume, "data", "_data/new", "_data/old");
+
+        assert!(inodes.get(replaced).is_none());
+    }
+}
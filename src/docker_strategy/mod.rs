@@ -1,10 +1,22 @@
 pub mod child_directories;
 pub(crate) mod docker;
+pub(crate) mod docker_events;
+pub mod docker_host;
+pub mod docker_options;
 pub mod docker_strategy;
+pub mod engines_strategy;
+pub mod file_handle;
+pub(crate) mod inode_table;
 pub mod parent_directories;
 
 pub mod containers;
+pub mod images;
+pub mod networks;
+pub mod pods;
 pub mod root_directory;
+pub mod volumes;
 
 pub(crate) use docker::Docker;
+pub use docker_options::DockerOptions;
 pub use docker_strategy::DockerStrategy;
+pub use engines_strategy::EnginesStrategy;
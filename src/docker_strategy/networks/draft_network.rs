@@ -0,0 +1,352 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use bollard::{
+    network::{CreateNetworkOptions, InspectNetworkOptions},
+    service::{Ipam, IpamConfig},
+};
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::OnceCell;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        networks::Network,
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const OPTIONS: &str = ".options";
+
+// Placeholder created by `mkdir` in the networks directory. The network is created the first
+// time anything other than its `.options` is looked up, as a bridge with default IPAM unless
+// the options say otherwise. Drafts share the inode of their network, like volume drafts.
+#[derive(Debug)]
+pub struct DraftNetwork {
+    ino: u64,
+    name: String,
+    created: SystemTime,
+    options: Arc<Mutex<Vec<u8>>>,
+    network: OnceCell<Network>,
+    inodes: Inodes,
+}
+
+impl From<DraftNetwork> for Box<dyn ChildDirectory> {
+    fn from(value: DraftNetwork) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for DraftNetwork {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Networks
+    }
+
+    fn as_network(&self) -> Option<&Network> {
+        self.network.get()
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if let Some(network) = self.network.get() {
+                return network.read_dir(path, docker).await;
+            }
+
+            if !path.is_empty() {
+                return Err(libc::ENOENT);
+            }
+
+            Ok(vec![
+                DirectoryEntry::new(self.ino, FileType::Directory, "."),
+                DirectoryEntry::new(
+                    ParentDirectories::Networks as u64,
+                    FileType::Directory,
+                    "..",
+                ),
+                DirectoryEntry::new(self.entry_ino(OPTIONS), FileType::RegularFile, OPTIONS),
+            ])
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            match self.network.get() {
+                None if join_path(path, name) == OPTIONS => self.getattr(OPTIONS),
+                _ => self.create(docker).await?.lookup(path, name, docker).await,
+            }
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match (self.network.get(), path) {
+            (_, "") => Ok(self.dir_attr()),
+            (Some(network), path) => network.getattr(path),
+            (None, OPTIONS) => Ok(FileAttr {
+                ino: self.entry_ino(OPTIONS),
+                size: self.options.lock().unwrap().len() as u64,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                ..self.dir_attr()
+            }),
+            (None, _) => Err(libc::ENOENT),
+        }
+    }
+
+    fn set_size(&self, path: &str, size: u64) -> Result<FileAttr, libc::c_int> {
+        match (self.network.get(), path) {
+            (Some(network), path) => return network.set_size(path, size),
+            (None, OPTIONS) => self.options.lock().unwrap().resize(size as usize, 0),
+            (None, _) => return Err(libc::EACCES),
+        }
+
+        self.getattr(path)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            match (self.network.get(), path) {
+                (Some(network), path) => network.open(path, docker).await,
+                (None, OPTIONS) => Ok(FileHandle::Buffer(self.options.clone())),
+                (None, _) => Err(libc::ENOENT),
+            }
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        self.network.get().ok_or(libc::EINVAL)?.readlink(path)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        match self.network.get() {
+            Some(network) => network.remove(path),
+            None => self.getattr(path).and(Err(libc::EPERM)),
+        }
+    }
+
+    fn symlink(&self, path: &str, link: &Path) -> Result<FileAttr, libc::c_int> {
+        self.network.get().ok_or(libc::EPERM)?.symlink(path, link)
+    }
+}
+
+impl DraftNetwork {
+    pub fn new(name: &str, inodes: &Inodes) -> Self {
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Network, name),
+            name: name.to_string(),
+            created: SystemTime::now(),
+            options: Arc::new(Mutex::new(Vec::new())),
+            network: OnceCell::new(),
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn is_created(&self) -> bool {
+        self.network.initialized()
+    }
+
+    async fn create(&self, docker: &bollard::Docker) -> Result<&Network, libc::c_int> {
+        self.network
+            .get_or_try_init(|| async {
+                let log_error = |e: bollard::errors::Error| {
+                    log::error!("Failed to create network {}: {}", self.name, e);
+                    errno_from_docker_error(&e)
+                };
+
+                let response = docker
+                    .create_network(self.parse_options()?)
+                    .await
+                    .map_err(log_error)?;
+                let network = docker
+                    .inspect_network(
+                        &response.id.unwrap_or_else(|| self.name.clone()),
+                        None::<InspectNetworkOptions<String>>,
+                    )
+                    .await
+                    .map_err(log_error)?;
+
+                Ok(Network::new(network, docker, &self.inodes))
+            })
+            .await
+    }
+
+    // Lines of `driver=<driver>`, `subnet=<cidr>`, `gateway=<address>`, `label=<key>=<value>`
+    // and `opt=<key>=<value>`, like the flags of `docker network create`.
+    fn parse_options(&self) -> Result<CreateNetworkOptions<String>, libc::c_int> {
+        let options = self.options.lock().unwrap();
+        let options = std::str::from_utf8(&options).map_err(|_| libc::EINVAL)?;
+
+        let mut create = CreateNetworkOptions {
+            name: self.name.clone(),
+            check_duplicate: true,
+            driver: String::from("bridge"),
+            ..Default::default()
+        };
+        let mut ipam = IpamConfig::default();
+
+        for line in options
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let pair = |value: &str| {
+                value
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            };
+
+            match line.split_once('=') {
+                Some(("driver", driver)) => create.driver = driver.to_string(),
+                Some(("subnet", subnet)) => ipam.subnet = Some(subnet.to_string()),
+                Some(("gateway", gateway)) => ipam.gateway = Some(gateway.to_string()),
+                Some(("label", label)) => {
+                    let (key, value) = pair(label).unwrap_or((label.to_string(), String::new()));
+                    create.labels.insert(key, value);
+                }
+                Some(("opt", opt)) => {
+                    let (key, value) = pair(opt).ok_or(libc::EINVAL)?;
+                    create.options.insert(key, value);
+                }
+                _ => {
+                    log::error!("Invalid option for network {}: {}", self.name, line);
+                    return Err(libc::EINVAL);
+                }
+            }
+        }
+
+        if ipam.subnet.is_some() || ipam.gateway.is_some() {
+            create.ipam = Ipam {
+                config: Some(vec![ipam]),
+                ..Default::default()
+            };
+        }
+
+        Ok(create)
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Network, &self.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker_strategy::inode_table::InodeTable;
+
+    fn draft(options: &str) -> DraftNetwork {
+        let inodes = Arc::new(Mutex::new(InodeTable::new()));
+        let draft = DraftNetwork::new("backend", &inodes);
+        *draft.options.lock().unwrap() = options.as_bytes().to_vec();
+        draft
+    }
+
+    #[test]
+    fn test_parse_options_defaults() {
+        let options = draft("").parse_options().unwrap();
+
+        assert_eq!(options.name, "backend");
+        assert_eq!(options.driver, "bridge");
+        assert!(options.check_duplicate);
+        assert!(options.ipam.config.is_none());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = draft(
+            "driver=macvlan\n\
+             subnet=10.1.0.0/16\n\
+             gateway=10.1.0.1\n\
+             # parent interface\n\
+             opt=parent=eth0\n\
+             label=team=web\n",
+        )
+        .parse_options()
+        .unwrap();
+
+        assert_eq!(options.driver, "macvlan");
+        assert_eq!(options.options["parent"], "eth0");
+        assert_eq!(options.labels["team"], "web");
+
+        let ipam = options.ipam.config.unwrap();
+        assert_eq!(ipam.len(), 1);
+        assert_eq!(ipam[0].subnet.as_deref(), Some("10.1.0.0/16"));
+        assert_eq!(ipam[0].gateway.as_deref(), Some("10.1.0.1"));
+    }
+
+    #[test]
+    fn test_parse_invalid_options() {
+        assert_eq!(
+            draft("internal=true").parse_options().err(),
+            Some(libc::EINVAL)
+        );
+        assert_eq!(draft("opt=eth0").parse_options().err(), Some(libc::EINVAL));
+        assert_eq!(
+            draft("driver=bridge\nlabel").parse_options().err(),
+            Some(libc::EINVAL)
+        );
+    }
+}
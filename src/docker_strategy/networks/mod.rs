@@ -0,0 +1,8 @@
+pub(crate) mod draft_network;
+pub(crate) mod network;
+pub(crate) mod network_file;
+pub(crate) mod networks_root;
+
+pub(crate) use draft_network::DraftNetwork;
+pub(crate) use network::Network;
+pub(crate) use network_file::NetworkFile;
@@ -0,0 +1,424 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use bollard::{
+    network::{ConnectNetworkOptions, DisconnectNetworkOptions, InspectNetworkOptions},
+    service::NetworkContainer,
+};
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+use tokio::{runtime::Handle, time::Instant};
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        inode_table::{InodeKey, InodeKind, Inodes},
+        networks::NetworkFile,
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::{DirectoryEntry, Invalidation},
+};
+
+const CONTAINERS: &str = "containers";
+const IPS: &str = "ips";
+
+// Containers join and leave networks often, the inspection is only reused for a short while.
+const TTL: Duration = Duration::from_secs(1);
+
+// Network as inspected, listing networks leaving out the containers attached to them.
+#[derive(Debug)]
+struct NetworkDetails {
+    network: bollard::service::Network,
+    loaded_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct Network {
+    ino: u64,
+    id: String,
+    name: String,
+    pub network: bollard::service::Network,
+    details: Arc<Mutex<Option<Arc<NetworkDetails>>>>,
+    docker: bollard::Docker,
+    inodes: Inodes,
+}
+
+impl From<Network> for Box<dyn ChildDirectory> {
+    fn from(value: Network) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for Network {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Networks
+    }
+
+    fn as_network(&self) -> Option<&Network> {
+        Some(self)
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            let kind = match path {
+                "" => return Ok(self.entries()),
+                CONTAINERS => FileType::Symlink,
+                IPS => FileType::RegularFile,
+                _ => return Err(libc::ENOTDIR),
+            };
+
+            let details = self.details(docker).await?;
+            let mut entries = vec![
+                DirectoryEntry::new(self.entry_ino(path), FileType::Directory, "."),
+                DirectoryEntry::new(self.ino, FileType::Directory, ".."),
+            ];
+
+            entries.extend(Self::endpoints(&details.network).map(|(name, _)| {
+                DirectoryEntry::new(self.entry_ino(&join_path(path, name)), kind, name)
+            }));
+
+            Ok(entries)
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            self.details(docker).await?;
+            self.getattr(&join_path(path, name))
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        let details = self.details.lock().unwrap().clone();
+        let network = details
+            .as_ref()
+            .map(|details| &details.network)
+            .unwrap_or(&self.network);
+
+        let file_attr = |size: usize| FileAttr {
+            ino: self.entry_ino(path),
+            size: size as u64,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            ..self.dir_attr()
+        };
+
+        match path.split_once('/') {
+            _ if path.is_empty() => Ok(self.dir_attr()),
+            None if path == CONTAINERS || path == IPS => Ok(FileAttr {
+                ino: self.entry_ino(path),
+                ..self.dir_attr()
+            }),
+            Some((CONTAINERS, name)) => {
+                Self::endpoint(network, name).ok_or(libc::ENOENT)?;
+                Ok(FileAttr {
+                    kind: FileType::Symlink,
+                    perm: 0o777,
+                    ..file_attr(Self::link(name).len())
+                })
+            }
+            Some((IPS, name)) => {
+                let endpoint = Self::endpoint(network, name).ok_or(libc::ENOENT)?;
+                Ok(file_attr(Self::addresses(endpoint).len()))
+            }
+            Some(_) => Err(libc::ENOENT),
+            None => {
+                let file = NetworkFile::try_from(path).map_err(|_| libc::ENOENT)?;
+                Ok(file_attr(file.content(network)?.len()))
+            }
+        }
+    }
+
+    fn set_size(&self, _path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            let details = self.details(docker).await?;
+
+            let content = match path.split_once('/') {
+                Some((IPS, name)) => {
+                    Self::addresses(Self::endpoint(&details.network, name).ok_or(libc::ENOENT)?)
+                }
+                Some(_) => return Err(libc::ENOENT),
+                None => NetworkFile::try_from(path)
+                    .map_err(|_| libc::ENOENT)?
+                    .content(&details.network)?,
+            };
+
+            Ok(FileHandle::Snapshot(Arc::new(content.into_bytes())))
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        match path.split_once('/') {
+            Some((CONTAINERS, name)) => {
+                self.getattr(path)?;
+                Ok(Self::link(name))
+            }
+            _ => Err(libc::EINVAL),
+        }
+    }
+
+    // Removing the symlink of a container disconnects it, other entries are views.
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        self.getattr(path)?;
+
+        let name = match path.split_once('/') {
+            Some((CONTAINERS, name)) => name,
+            _ => return Err(libc::EPERM),
+        };
+
+        let result = Handle::current().block_on(self.docker.disconnect_network(
+            &self.id,
+            DisconnectNetworkOptions {
+                container: name,
+                force: false,
+            },
+        ));
+        self.details.lock().unwrap().take();
+
+        result.map_err(|e| {
+            log::error!("Failed to disconnect {} from {}: {}", name, self.name, e);
+            errno_from_docker_error(&e)
+        })
+    }
+
+    // Containers are connected by linking to their directory, named after the container.
+    fn symlink(&self, path: &str, link: &Path) -> Result<FileAttr, libc::c_int> {
+        let name = match path.split_once('/') {
+            Some((CONTAINERS, name)) => name,
+            _ => return Err(libc::EPERM),
+        };
+
+        if link.file_name().and_then(|link| link.to_str()) != Some(name) {
+            return Err(libc::EINVAL);
+        }
+
+        let handle = Handle::current();
+        let result = handle.block_on(self.docker.connect_network(
+            &self.id,
+            ConnectNetworkOptions {
+                container: name,
+                endpoint_config: Default::default(),
+            },
+        ));
+        self.details.lock().unwrap().take();
+
+        result.map_err(|e| {
+            log::error!("Failed to connect {} to {}: {}", name, self.name, e);
+            errno_from_docker_error(&e)
+        })?;
+
+        handle.block_on(self.details(&self.docker))?;
+        self.getattr(path)
+    }
+}
+
+impl Network {
+    pub fn new(
+        network: bollard::service::Network,
+        docker: &bollard::Docker,
+        inodes: &Inodes,
+    ) -> Self {
+        let id = network.id.clone().unwrap_or_default();
+        let name = network.name.clone().unwrap_or_else(|| id.clone());
+
+        // Networks are pinned by name, like volumes, so that drafts share the inode of their
+        // network.
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Network, &name),
+            name,
+            id,
+            network,
+            details: Arc::new(Mutex::new(None)),
+            docker: docker.clone(),
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn keep_state(&mut self, previous: &Network) {
+        self.details = previous.details.clone();
+    }
+
+    // Forgets the inspection once a container joined or left the network, returning the entries
+    // of the container the kernel may still hold.
+    pub fn expire(&self, container: Option<&str>) -> Vec<Invalidation> {
+        self.details.lock().unwrap().take();
+
+        let inodes = self.inodes.lock().unwrap();
+        container
+            .into_iter()
+            .flat_map(|container| {
+                [CONTAINERS, IPS].into_iter().filter_map(|dir| {
+                    inodes
+                        .find(&InodeKey::new(InodeKind::Network, &self.name, dir))
+                        .map(|parent| Invalidation::Entry {
+                            parent,
+                            name: container.into(),
+                        })
+                })
+            })
+            .collect()
+    }
+
+    async fn details(&self, docker: &bollard::Docker) -> Result<Arc<NetworkDetails>, libc::c_int> {
+        if let Some(details) = self.details.lock().unwrap().as_ref() {
+            if details.loaded_at.elapsed() < TTL {
+                return Ok(details.clone());
+            }
+        }
+
+        let network = docker
+            .inspect_network(&self.id, None::<InspectNetworkOptions<String>>)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to inspect network {}: {}", self.name, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        let details = Arc::new(NetworkDetails {
+            network,
+            loaded_at: Instant::now(),
+        });
+        *self.details.lock().unwrap() = Some(details.clone());
+
+        Ok(details)
+    }
+
+    // Attached containers by name, sorted so that listings are stable.
+    fn endpoints(
+        network: &bollard::service::Network,
+    ) -> impl Iterator<Item = (&String, &NetworkContainer)> {
+        let mut endpoints: Vec<(&String, &NetworkContainer)> = network
+            .containers
+            .iter()
+            .flatten()
+            .filter_map(|(_, endpoint)| endpoint.name.as_ref().map(|name| (name, endpoint)))
+            .collect();
+        endpoints.sort_by(|a, b| a.0.cmp(b.0));
+
+        endpoints.into_iter()
+    }
+
+    fn endpoint<'a>(
+        network: &'a bollard::service::Network,
+        name: &str,
+    ) -> Option<&'a NetworkContainer> {
+        Self::endpoints(network)
+            .find(|(endpoint_name, _)| endpoint_name.as_str() == name)
+            .map(|(_, endpoint)| endpoint)
+    }
+
+    // From `/networks/<network>/containers/<name>` back to `/containers/<name>`.
+    fn link(name: &str) -> String {
+        format!(
+            "../../../{}/{}",
+            ParentDirectories::Containers.to_string(),
+            name
+        )
+    }
+
+    // Addresses of a container on the network, with their prefix length.
+    fn addresses(endpoint: &NetworkContainer) -> String {
+        [&endpoint.ipv4_address, &endpoint.ipv6_address]
+            .into_iter()
+            .flatten()
+            .filter(|address| !address.is_empty())
+            .map(|address| format!("{}\n", address))
+            .collect()
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.ino, FileType::Directory, "."),
+            DirectoryEntry::new(
+                ParentDirectories::Networks as u64,
+                FileType::Directory,
+                "..",
+            ),
+        ];
+
+        entries.extend(NetworkFile::iterator().map(|file| {
+            DirectoryEntry::new(
+                self.entry_ino(file.as_str()),
+                FileType::RegularFile,
+                file.as_str(),
+            )
+        }));
+
+        entries.extend(
+            [CONTAINERS, IPS]
+                .iter()
+                .map(|dir| DirectoryEntry::new(self.entry_ino(dir), FileType::Directory, dir)),
+        );
+
+        entries
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Network, &self.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::docker_strategy::docker_strategy::DockerError;
+
+#[derive(Debug, Clone, Copy)]
+pub enum NetworkFile {
+    Inspect,
+    Driver,
+    Subnet,
+    Gateway,
+    Scope,
+}
+
+impl TryFrom<&str> for NetworkFile {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "inspect.json" => Ok(NetworkFile::Inspect),
+            "driver" => Ok(NetworkFile::Driver),
+            "subnet" => Ok(NetworkFile::Subnet),
+            "gateway" => Ok(NetworkFile::Gateway),
+            "scope" => Ok(NetworkFile::Scope),
+            _ => Err(DockerError::UnknownChildEntry),
+        }
+    }
+}
+
+impl NetworkFile {
+    pub fn iterator() -> impl Iterator<Item = NetworkFile> {
+        [
+            NetworkFile::Inspect,
+            NetworkFile::Driver,
+            NetworkFile::Subnet,
+            NetworkFile::Gateway,
+            NetworkFile::Scope,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkFile::Inspect => "inspect.json",
+            NetworkFile::Driver => "driver",
+            NetworkFile::Subnet => "subnet",
+            NetworkFile::Gateway => "gateway",
+            NetworkFile::Scope => "scope",
+        }
+    }
+
+    // Networks may have several IPAM configurations, one line is given for each of them.
+    pub fn content(&self, network: &bollard::service::Network) -> Result<String, libc::c_int> {
+        let ipam_lines = |field: fn(&bollard::service::IpamConfig) -> Option<&String>| {
+            network
+                .ipam
+                .as_ref()
+                .and_then(|ipam| ipam.config.as_ref())
+                .map(|configs| {
+                    configs
+                        .iter()
+                        .filter_map(field)
+                        .map(|value| format!("{}\n", value))
+                        .collect::<String>()
+                })
+                .unwrap_or_default()
+        };
+
+        match self {
+            NetworkFile::Inspect => serde_json::to_string_pretty(network)
+                .map(|inspect| inspect + "\n")
+                .map_err(|e| {
+                    log::error!("Failed to serialize network: {}", e);
+                    libc::EIO
+                }),
+            NetworkFile::Driver => Ok(format!("{}\n", network.driver.as_deref().unwrap_or(""))),
+            NetworkFile::Subnet => Ok(ipam_lines(|config| config.subnet.as_ref())),
+            NetworkFile::Gateway => Ok(ipam_lines(|config| config.gateway.as_ref())),
+            NetworkFile::Scope => Ok(format!("{}\n", network.scope.as_deref().unwrap_or(""))),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use fuser::{FileAttr, FileType};
+use std::{sync::Arc, time::UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        docker::errno_from_docker_error,
+        networks::{DraftNetwork, Network},
+        parent_directories::ParentDirectories,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+// Docker refuses to remove networks with containers attached or defined by itself.
+fn errno_from_remove_error(error: &bollard::errors::Error) -> libc::c_int {
+    match error {
+        bollard::errors::Error::DockerResponseServerError {
+            status_code: 403,
+            message,
+        } if message.contains("active endpoints") => libc::ENOTEMPTY,
+        bollard::errors::Error::DockerResponseServerError {
+            status_code: 403, ..
+        } => libc::EPERM,
+        e => errno_from_docker_error(e),
+    }
+}
+
+impl ParentDirectories {
+    pub(crate) async fn networks_root_lookup(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_networks().await {
+            log::error!("Failed to update networks: {}", e);
+        }
+
+        let network_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let attr = docker
+            .get::<Network>()
+            .into_iter()
+            .map(|network| network as &dyn ChildDirectory)
+            .chain(
+                docker
+                    .get::<DraftNetwork>()
+                    .into_iter()
+                    .map(|draft| draft as &dyn ChildDirectory),
+            )
+            .find(|network| network.get_name() == network_name)
+            .ok_or(libc::ENOENT)?
+            .getattr("")?;
+        docker.remember(attr.ino);
+
+        Ok(attr)
+    }
+
+    pub(crate) async fn networks_root_mkdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_networks().await {
+            log::error!("Failed to update networks: {}", e);
+        }
+
+        let network_name = name.to_str().ok_or(libc::EINVAL)?;
+
+        let exists = docker
+            .get::<Network>()
+            .into_iter()
+            .any(|network| network.get_name() == network_name)
+            || docker
+                .get::<DraftNetwork>()
+                .into_iter()
+                .any(|draft| draft.get_name() == network_name);
+        if exists {
+            return Err(libc::EEXIST);
+        }
+
+        let draft = DraftNetwork::new(network_name, docker.get_inodes());
+        let attr = draft.dir_attr();
+        docker.insert_child(draft.into());
+
+        Ok(attr)
+    }
+
+    pub(crate) async fn networks_root_rmdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_networks().await {
+            log::error!("Failed to update networks: {}", e);
+        }
+
+        let network_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        // Drafts only exist in the mount until their network is created.
+        if let Some(ino) = docker
+            .get::<DraftNetwork>()
+            .into_iter()
+            .find(|draft| draft.get_name() == network_name && !draft.is_created())
+            .map(|draft| draft.get_ino())
+        {
+            docker.remove_child(ino);
+            return Ok(());
+        }
+
+        let (ino, id) = docker
+            .get::<Network>()
+            .into_iter()
+            .find(|network| network.get_name() == network_name)
+            .map(|network| (network.get_ino(), network.get_id().clone()))
+            .ok_or(libc::ENOENT)?;
+
+        docker.get_docker().remove_network(&id).await.map_err(|e| {
+            log::error!("Failed to remove network {}: {}", network_name, e);
+            errno_from_remove_error(&e)
+        })?;
+
+        docker.remove_child(ino);
+        Ok(())
+    }
+
+    pub(crate) fn networks_root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.into(),
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH, // 1970-01-01 00:00:00
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o777,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+
+    pub(crate) async fn networks_root_read_dir(
+        &self,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let mut entries = vec![
+            DirectoryEntry::new(ParentDirectories::Networks as u64, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
+        ];
+
+        let mut docker = docker.lock().await;
+
+        if let Err(error) = docker.update_networks().await {
+            log::debug!("Failed to update networks, error: {}", error);
+        }
+
+        docker.get::<Network>().into_iter().for_each(|network| {
+            entries.push(DirectoryEntry::new(
+                network.get_ino(),
+                FileType::Directory,
+                network.get_name(),
+            ))
+        });
+
+        docker
+            .get::<DraftNetwork>()
+            .into_iter()
+            .filter(|draft| !draft.is_created())
+            .for_each(|draft| {
+                entries.push(DirectoryEntry::new(
+                    draft.get_ino(),
+                    FileType::Directory,
+                    draft.get_name(),
+                ))
+            });
+
+        entries[2..].sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entries)
+    }
+}
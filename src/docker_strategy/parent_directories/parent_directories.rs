@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use super::docker_strategy::DockerError;
+use crate::fuse_handler::DirectoryEntry;
 use fuser::FileAttr;
 use tokio::sync::Mutex;
 
@@ -11,6 +12,7 @@ pub enum ParentDirectories {
     Images = 3,
     Volumes = 4,
     Networks = 5,
+    Pods = 6,
 }
 
 impl From<ParentDirectories> for u64 {
@@ -21,6 +23,7 @@ impl From<ParentDirectories> for u64 {
             ParentDirectories::Images => 3,
             ParentDirectories::Volumes => 4,
             ParentDirectories::Networks => 5,
+            ParentDirectories::Pods => 6,
         }
     }
 }
@@ -33,6 +36,7 @@ impl From<&ParentDirectories> for u64 {
             ParentDirectories::Images => 3,
             ParentDirectories::Volumes => 4,
             ParentDirectories::Networks => 5,
+            ParentDirectories::Pods => 6,
         }
     }
 }
@@ -47,6 +51,7 @@ impl TryFrom<u64> for ParentDirectories {
             3 => Ok(ParentDirectories::Images),
             4 => Ok(ParentDirectories::Volumes),
             5 => Ok(ParentDirectories::Networks),
+            6 => Ok(ParentDirectories::Pods),
             _ => Err(DockerError::UnknownParentDirectory),
         }
     }
@@ -62,6 +67,7 @@ impl TryFrom<&str> for ParentDirectories {
             "images" => Ok(ParentDirectories::Images),
             "volumes" => Ok(ParentDirectories::Volumes),
             "networks" => Ok(ParentDirectories::Networks),
+            "pods" => Ok(ParentDirectories::Pods),
             _ => Err(DockerError::UnknownParentDirectory),
         }
     }
@@ -76,6 +82,7 @@ impl ParentDirectories {
             ParentDirectories::Images,
             ParentDirectories::Volumes,
             ParentDirectories::Networks,
+            ParentDirectories::Pods,
         ]
         .iter()
         .copied()
@@ -88,59 +95,105 @@ impl ParentDirectories {
             ParentDirectories::Images => String::from("images"),
             ParentDirectories::Volumes => String::from("volumes"),
             ParentDirectories::Networks => String::from("networks"),
+            ParentDirectories::Pods => String::from("pods"),
         }
     }
 
     pub(crate) fn attr(&self) -> FileAttr {
         match self {
             ParentDirectories::Containers => self.containers_root_attr(),
-            ParentDirectories::Images => unimplemented!(),
-            ParentDirectories::Volumes => unimplemented!(),
-            ParentDirectories::Networks => unimplemented!(),
+            ParentDirectories::Images => self.images_root_attr(),
+            ParentDirectories::Volumes => self.volumes_root_attr(),
+            ParentDirectories::Networks => self.networks_root_attr(),
+            ParentDirectories::Pods => self.pods_root_attr(),
             ParentDirectories::Root => self.root_attr(),
         }
     }
 
     pub(crate) async fn read_dir(
         &self,
-        offset: i64,
-        reply: &mut fuser::ReplyDirectory,
         docker: Arc<Mutex<super::Docker>>,
-    ) -> Result<(), libc::c_int> {
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
         match self {
-            ParentDirectories::Containers => {
-                self.containers_root_read_dir(offset, reply, docker).await
+            ParentDirectories::Containers => self.containers_root_read_dir(docker).await,
+            ParentDirectories::Images => self.images_root_read_dir(docker).await,
+            ParentDirectories::Volumes => self.volumes_root_read_dir(docker).await,
+            ParentDirectories::Networks => self.networks_root_read_dir(docker).await,
+            ParentDirectories::Pods => self.pods_root_read_dir(docker).await,
+            ParentDirectories::Root => {
+                let podman = docker.lock().await.get_options().podman.is_some();
+                Ok(self.root_read_dir(podman))
             }
-            ParentDirectories::Images => unimplemented!(),
-            ParentDirectories::Volumes => unimplemented!(),
-            ParentDirectories::Networks => unimplemented!(),
-            ParentDirectories::Root => self.root_read_dir(offset, reply),
         }
     }
 
     pub(crate) async fn lookup(
         &self,
         name: &std::ffi::OsStr,
-        reply: fuser::ReplyEntry,
         docker: Arc<Mutex<super::Docker>>,
-    ) -> Result<(), libc::c_int> {
+    ) -> Result<FileAttr, libc::c_int> {
         match self {
-            ParentDirectories::Containers => {
-                Self::containers_root_lookup(name, reply, docker).await
+            ParentDirectories::Containers => Self::containers_root_lookup(name, docker).await,
+            ParentDirectories::Images => Self::images_root_lookup(name, docker).await,
+            ParentDirectories::Volumes => Self::volumes_root_lookup(name, docker).await,
+            ParentDirectories::Networks => Self::networks_root_lookup(name, docker).await,
+            ParentDirectories::Pods => Self::pods_root_lookup(name, docker).await,
+            ParentDirectories::Root => {
+                let podman = docker.lock().await.get_options().podman.is_some();
+                Self::root_lookup(name, podman)
             }
-            ParentDirectories::Images => unimplemented!(),
-            ParentDirectories::Volumes => unimplemented!(),
-            ParentDirectories::Networks => unimplemented!(),
-            ParentDirectories::Root => Self::root_lookup(name, reply),
         }
     }
 
-    pub(crate) fn ino_from_docker_id(name: &str) -> u64 {
-        let mut ino = 0;
-        for c in name.chars().take(8) {
-            ino = ino << 8;
-            ino += c as u64;
+    pub(crate) async fn mkdir(
+        &self,
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<super::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        match self {
+            ParentDirectories::Containers => Self::containers_root_mkdir(name, docker).await,
+            ParentDirectories::Images => Self::images_root_mkdir(name, docker).await,
+            ParentDirectories::Volumes => Self::volumes_root_mkdir(name, docker).await,
+            ParentDirectories::Networks => Self::networks_root_mkdir(name, docker).await,
+            _ => Err(libc::EPERM),
+        }
+    }
+
+    pub(crate) async fn rmdir(
+        &self,
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<super::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        match self {
+            ParentDirectories::Containers => Self::containers_root_rmdir(name, docker).await,
+            ParentDirectories::Images => Self::images_root_rmdir(name, docker).await,
+            ParentDirectories::Volumes => Self::volumes_root_rmdir(name, docker).await,
+            ParentDirectories::Networks => Self::networks_root_rmdir(name, docker).await,
+            _ => Err(libc::EPERM),
+        }
+    }
+
+    pub(crate) async fn unlink(
+        &self,
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<super::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        match self {
+            ParentDirectories::Images => Self::images_root_unlink(name, docker).await,
+            // Everything else listed by parent directories is a directory.
+            _ => Err(libc::EISDIR),
+        }
+    }
+
+    pub(crate) async fn symlink(
+        &self,
+        name: &std::ffi::OsStr,
+        link: &Path,
+        docker: Arc<Mutex<super::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        match self {
+            ParentDirectories::Images => Self::images_root_symlink(name, link, docker).await,
+            _ => Err(libc::EPERM),
         }
-        ino
     }
 }
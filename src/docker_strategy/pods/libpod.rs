@@ -0,0 +1,64 @@
+use std::io;
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UnixStream},
+};
+
+// Pods are served by the API of podman itself, next to its docker compatible API.
+const API_VERSION: &str = "v4.0.0";
+
+// Reports of `GET /libpod/pods/json`, with the containers of every pod.
+pub async fn list_pods(address: &str) -> io::Result<Vec<serde_json::Value>> {
+    let body = get(address, &format!("/{}/libpod/pods/json", API_VERSION)).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+// Answered by podman only, docker not serving its API.
+pub async fn ping(address: &str) -> io::Result<()> {
+    get(address, &format!("/{}/libpod/_ping", API_VERSION)).await?;
+    Ok(())
+}
+
+// bollard only speaks the docker API, requests are written by hand. HTTP/1.0 keeps the response
+// unchunked, its body ending with the connection.
+async fn get(address: &str, path: &str) -> io::Result<Vec<u8>> {
+    let request = format!("GET {} HTTP/1.0\r\nHost: podman\r\n\r\n", path);
+
+    let response = match address.split_once("://") {
+        Some(("unix", socket)) => exchange(UnixStream::connect(socket).await?, &request).await?,
+        Some(("tcp" | "http", host)) => {
+            let host = host.trim_end_matches('/');
+            exchange(TcpStream::connect(host).await?, &request).await?
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported podman host {}", address),
+            ))
+        }
+    };
+
+    let end_of_head = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated response"))?;
+    let head = String::from_utf8_lossy(&response[..end_of_head]);
+    let status = head.lines().next().unwrap_or_default();
+
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(response[end_of_head + 4..].to_vec()),
+        _ => Err(io::Error::other(format!("Podman answered {}", status))),
+    }
+}
+
+async fn exchange<S>(mut stream: S, request: &str) -> io::Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    Ok(response)
+}
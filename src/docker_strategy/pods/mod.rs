@@ -0,0 +1,7 @@
+pub(crate) mod libpod;
+pub(crate) mod pod;
+pub(crate) mod pod_file;
+pub(crate) mod pods_root;
+
+pub(crate) use pod::Pod;
+pub(crate) use pod_file::PodFile;
@@ -0,0 +1,243 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+        pods::PodFile,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const CONTAINERS: &str = "containers";
+
+// Pod as reported by podman, its containers being listed in the report. Pods are views, they
+// are managed with podman itself.
+#[derive(Debug)]
+pub struct Pod {
+    ino: u64,
+    id: String,
+    name: String,
+    pub pod: serde_json::Value,
+    inodes: Inodes,
+}
+
+impl From<Pod> for Box<dyn ChildDirectory> {
+    fn from(value: Pod) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for Pod {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Pods
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            match path {
+                "" => Ok(self.entries()),
+                CONTAINERS => {
+                    let mut entries = vec![
+                        DirectoryEntry::new(self.entry_ino(path), FileType::Directory, "."),
+                        DirectoryEntry::new(self.ino, FileType::Directory, ".."),
+                    ];
+
+                    entries.extend(self.containers().into_iter().map(|name| {
+                        DirectoryEntry::new(
+                            self.entry_ino(&join_path(path, name)),
+                            FileType::Symlink,
+                            name,
+                        )
+                    }));
+
+                    Ok(entries)
+                }
+                _ => Err(libc::ENOTDIR),
+            }
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move { self.getattr(&join_path(path, name)) }.boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        let file_attr = |size: usize| FileAttr {
+            ino: self.entry_ino(path),
+            size: size as u64,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            ..self.dir_attr()
+        };
+
+        match path.split_once('/') {
+            _ if path.is_empty() => Ok(self.dir_attr()),
+            None if path == CONTAINERS => Ok(FileAttr {
+                ino: self.entry_ino(path),
+                ..self.dir_attr()
+            }),
+            Some((CONTAINERS, name)) if self.containers().contains(&name) => Ok(FileAttr {
+                kind: FileType::Symlink,
+                perm: 0o777,
+                ..file_attr(Self::link(name).len())
+            }),
+            Some(_) => Err(libc::ENOENT),
+            None => {
+                let file = PodFile::try_from(path).map_err(|_| libc::ENOENT)?;
+                Ok(file_attr(file.content(&self.pod)?.len()))
+            }
+        }
+    }
+
+    fn set_size(&self, _path: &str, _size: u64) -> Result<FileAttr, libc::c_int> {
+        Err(libc::EACCES)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            let file = PodFile::try_from(path).map_err(|_| libc::ENOENT)?;
+            let content = file.content(&self.pod)?;
+
+            Ok(FileHandle::Snapshot(Arc::new(content.into_bytes())))
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        match path.split_once('/') {
+            Some((CONTAINERS, name)) => {
+                self.getattr(path)?;
+                Ok(Self::link(name))
+            }
+            _ => Err(libc::EINVAL),
+        }
+    }
+}
+
+impl Pod {
+    // Reports without an id can't be told apart, they are skipped.
+    pub fn new(pod: serde_json::Value, inodes: &Inodes) -> Option<Self> {
+        let id = pod["Id"].as_str().filter(|id| !id.is_empty())?.to_string();
+        let name = pod["Name"]
+            .as_str()
+            .map(String::from)
+            .unwrap_or_else(|| id.clone());
+
+        Some(Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Pod, &id),
+            id,
+            name,
+            pod,
+            inodes: inodes.clone(),
+        })
+    }
+
+    // Names of the containers of the pod, sorted so that listings are stable.
+    fn containers(&self) -> Vec<&str> {
+        let mut containers: Vec<&str> = self.pod["Containers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|container| container["Names"].as_str())
+            .collect();
+        containers.sort();
+
+        containers
+    }
+
+    // From `/pods/<pod>/containers/<name>` back to `/containers/<name>`.
+    fn link(name: &str) -> String {
+        format!(
+            "../../../{}/{}",
+            ParentDirectories::Containers.to_string(),
+            name
+        )
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.ino, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Pods as u64, FileType::Directory, ".."),
+        ];
+
+        entries.extend(PodFile::iterator().map(|file| {
+            DirectoryEntry::new(
+                self.entry_ino(file.as_str()),
+                FileType::RegularFile,
+                file.as_str(),
+            )
+        }));
+
+        entries.push(DirectoryEntry::new(
+            self.entry_ino(CONTAINERS),
+            FileType::Directory,
+            CONTAINERS,
+        ));
+
+        entries
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Pod, &self.id, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
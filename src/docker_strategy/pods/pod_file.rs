@@ -0,0 +1,62 @@
+use crate::docker_strategy::docker_strategy::DockerError;
+
+#[derive(Debug, Clone, Copy)]
+pub enum PodFile {
+    Inspect,
+    Id,
+    Status,
+    Infra,
+}
+
+impl TryFrom<&str> for PodFile {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "inspect.json" => Ok(PodFile::Inspect),
+            "id" => Ok(PodFile::Id),
+            "status" => Ok(PodFile::Status),
+            "infra" => Ok(PodFile::Infra),
+            _ => Err(DockerError::UnknownChildEntry),
+        }
+    }
+}
+
+impl PodFile {
+    pub fn iterator() -> impl Iterator<Item = PodFile> {
+        [
+            PodFile::Inspect,
+            PodFile::Id,
+            PodFile::Status,
+            PodFile::Infra,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PodFile::Inspect => "inspect.json",
+            PodFile::Id => "id",
+            PodFile::Status => "status",
+            PodFile::Infra => "infra",
+        }
+    }
+
+    // `infra` holds the id of the container keeping the namespaces of the pod.
+    pub fn content(&self, pod: &serde_json::Value) -> Result<String, libc::c_int> {
+        let field = |name: &str| format!("{}\n", pod[name].as_str().unwrap_or(""));
+
+        match self {
+            PodFile::Inspect => serde_json::to_string_pretty(pod)
+                .map(|inspect| inspect + "\n")
+                .map_err(|e| {
+                    log::error!("Failed to serialize pod: {}", e);
+                    libc::EIO
+                }),
+            PodFile::Id => Ok(field("Id")),
+            PodFile::Status => Ok(field("Status")),
+            PodFile::Infra => Ok(field("InfraId")),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use fuser::{FileAttr, FileType};
+use std::{sync::Arc, time::UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        parent_directories::ParentDirectories, pods::Pod,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+impl ParentDirectories {
+    pub(crate) async fn pods_root_lookup(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_pods().await {
+            log::error!("Failed to update pods: {}", e);
+        }
+
+        let pod_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let attr = docker
+            .get::<Pod>()
+            .into_iter()
+            .find(|pod| pod.get_name() == pod_name)
+            .ok_or(libc::ENOENT)?
+            .getattr("")?;
+        docker.remember(attr.ino);
+
+        Ok(attr)
+    }
+
+    pub(crate) fn pods_root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.into(),
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH, // 1970-01-01 00:00:00
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o555,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+
+    pub(crate) async fn pods_root_read_dir(
+        &self,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let mut entries = vec![
+            DirectoryEntry::new(ParentDirectories::Pods as u64, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
+        ];
+
+        let mut docker = docker.lock().await;
+
+        if let Err(e) = docker.update_pods().await {
+            log::error!("Failed to update pods: {}", e);
+        }
+
+        docker.get::<Pod>().into_iter().for_each(|pod| {
+            entries.push(DirectoryEntry::new(
+                pod.get_ino(),
+                FileType::Directory,
+                pod.get_name(),
+            ))
+        });
+
+        entries[2..].sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entries)
+    }
+}
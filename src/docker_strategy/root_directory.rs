@@ -1,38 +1,24 @@
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::UNIX_EPOCH;
 
 use fuser::{FileAttr, FileType};
 
-use crate::docker_strategy::parent_directories::ParentDirectories;
+use crate::{docker_strategy::parent_directories::ParentDirectories, fuse_handler::DirectoryEntry};
 
 impl ParentDirectories {
+    // Pods are only listed when mounting podman.
     pub(crate) fn root_lookup(
         name: &std::ffi::OsStr,
-        reply: fuser::ReplyEntry,
-    ) -> Result<(), libc::c_int> {
-        // TODO: Check if there is RFC 3137 in nightly for let else or a better workaround ?
-
-        let name = match name.to_str() {
-            Some(str) => str,
-            _ => {
-                reply.error(libc::ENOENT);
-                return Ok(());
-            }
-        };
-
-        match name.try_into() {
-            Ok(ParentDirectories::Containers) => {
-                reply.entry(
-                    &Duration::from_secs(1),
-                    &ParentDirectories::Containers.attr(),
-                    0,
-                );
-                Ok(())
-            }
-
-            _ => {
-                reply.error(libc::ENOENT);
-                Ok(())
-            }
+        podman: bool,
+    ) -> Result<FileAttr, libc::c_int> {
+        match name.to_str().map(ParentDirectories::try_from) {
+            Some(Ok(
+                parent @ (ParentDirectories::Containers
+                | ParentDirectories::Images
+                | ParentDirectories::Volumes
+                | ParentDirectories::Networks),
+            )) => Ok(parent.attr()),
+            Some(Ok(parent @ ParentDirectories::Pods)) if podman => Ok(parent.attr()),
+            _ => Err(libc::ENOENT),
         }
     }
 
@@ -56,40 +42,27 @@ impl ParentDirectories {
         }
     }
 
-    pub(crate) fn root_read_dir(
-        &self,
-        offset: i64,
-        reply: &mut fuser::ReplyDirectory,
-    ) -> Result<(), libc::c_int> {
-        let entries = [
-            (ParentDirectories::Root as u64, ".".to_string()),
-            (ParentDirectories::Root as u64, "..".to_string()),
-            (
-                ParentDirectories::Containers as u64,
-                ParentDirectories::Containers.to_string(),
-            ),
-            (
-                ParentDirectories::Images as u64,
-                ParentDirectories::Images.to_string(),
-            ),
-            (
-                ParentDirectories::Volumes as u64,
-                ParentDirectories::Volumes.to_string(),
-            ),
-            (
-                ParentDirectories::Networks as u64,
-                ParentDirectories::Networks.to_string(),
-            ),
+    pub(crate) fn root_read_dir(&self, podman: bool) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
         ];
 
-        for (i, (ino, name)) in entries.iter().enumerate().skip(offset as usize) {
-            if i as i64 >= offset {
-                if reply.add(*ino, i as i64 + 1, FileType::Directory, name) {
-                    break;
-                }
-            }
-        }
+        entries.extend(
+            [
+                ParentDirectories::Containers,
+                ParentDirectories::Images,
+                ParentDirectories::Volumes,
+                ParentDirectories::Networks,
+                ParentDirectories::Pods,
+            ]
+            .iter()
+            .filter(|parent| podman || !matches!(parent, ParentDirectories::Pods))
+            .map(|parent| {
+                DirectoryEntry::new(parent.into(), FileType::Directory, &parent.to_string())
+            }),
+        );
 
-        Ok(())
+        entries
     }
 }
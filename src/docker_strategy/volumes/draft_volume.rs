@@ -0,0 +1,332 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use bollard::volume::CreateVolumeOptions;
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::OnceCell;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        docker::errno_from_docker_error,
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+        volumes::Volume,
+        DockerOptions,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const OPTIONS: &str = ".options";
+
+// Placeholder created by `mkdir` in the volumes directory. The volume is created the first time
+// anything other than its `.options` is looked up, with the options written by then. Drafts
+// share the inode of their volume, so that they are replaced by it on the next refresh.
+#[derive(Debug)]
+pub struct DraftVolume {
+    ino: u64,
+    name: String,
+    created: SystemTime,
+    options: Arc<Mutex<Vec<u8>>>,
+    volume: OnceCell<Volume>,
+    docker_options: DockerOptions,
+    inodes: Inodes,
+}
+
+impl From<DraftVolume> for Box<dyn ChildDirectory> {
+    fn from(value: DraftVolume) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for DraftVolume {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Volumes
+    }
+
+    fn as_volume(&self) -> Option<&Volume> {
+        self.volume.get()
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            if let Some(volume) = self.volume.get() {
+                return volume.read_dir(path, docker).await;
+            }
+
+            if !path.is_empty() {
+                return Err(libc::ENOENT);
+            }
+
+            Ok(vec![
+                DirectoryEntry::new(self.ino, FileType::Directory, "."),
+                DirectoryEntry::new(ParentDirectories::Volumes as u64, FileType::Directory, ".."),
+                DirectoryEntry::new(self.entry_ino(OPTIONS), FileType::RegularFile, OPTIONS),
+            ])
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            match self.volume.get() {
+                None if join_path(path, name) == OPTIONS => self.getattr(OPTIONS),
+                _ => self.create(docker).await?.lookup(path, name, docker).await,
+            }
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match (self.volume.get(), path) {
+            (_, "") => Ok(self.dir_attr()),
+            (Some(volume), path) => volume.getattr(path),
+            (None, OPTIONS) => Ok(FileAttr {
+                ino: self.entry_ino(OPTIONS),
+                size: self.options.lock().unwrap().len() as u64,
+                kind: FileType::RegularFile,
+                perm: 0o644,
+                nlink: 1,
+                ..self.dir_attr()
+            }),
+            (None, _) => Err(libc::ENOENT),
+        }
+    }
+
+    fn set_size(&self, path: &str, size: u64) -> Result<FileAttr, libc::c_int> {
+        match (self.volume.get(), path) {
+            (Some(volume), path) => return volume.set_size(path, size),
+            (None, OPTIONS) => self.options.lock().unwrap().resize(size as usize, 0),
+            (None, _) => return Err(libc::EACCES),
+        }
+
+        self.getattr(path)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            match (self.volume.get(), path) {
+                (Some(volume), path) => volume.open(path, docker).await,
+                (None, OPTIONS) => Ok(FileHandle::Buffer(self.options.clone())),
+                (None, _) => Err(libc::ENOENT),
+            }
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        self.volume.get().ok_or(libc::EINVAL)?.readlink(path)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        match self.volume.get() {
+            Some(volume) => volume.remove(path),
+            None => self.getattr(path).and(Err(libc::EPERM)),
+        }
+    }
+
+    fn create(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        self.volume.get().ok_or(libc::EACCES)?.create(path)
+    }
+
+    fn mkdir(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        self.volume.get().ok_or(libc::EPERM)?.mkdir(path)
+    }
+
+    fn rename(&self, path: &str, new_path: &str) -> Result<(), libc::c_int> {
+        self.volume.get().ok_or(libc::EPERM)?.rename(path, new_path)
+    }
+}
+
+impl DraftVolume {
+    pub fn new(name: &str, inodes: &Inodes, docker_options: &DockerOptions) -> Self {
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Volume, name),
+            name: name.to_string(),
+            created: SystemTime::now(),
+            options: Arc::new(Mutex::new(Vec::new())),
+            volume: OnceCell::new(),
+            docker_options: docker_options.clone(),
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn is_created(&self) -> bool {
+        self.volume.initialized()
+    }
+
+    async fn create(&self, docker: &bollard::Docker) -> Result<&Volume, libc::c_int> {
+        self.volume
+            .get_or_try_init(|| async {
+                let volume = docker
+                    .create_volume(self.parse_options()?)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Failed to create volume {}: {}", self.name, e);
+                        errno_from_docker_error(&e)
+                    })?;
+
+                Ok(Volume::new(
+                    volume,
+                    docker,
+                    &self.inodes,
+                    &self.docker_options,
+                ))
+            })
+            .await
+    }
+
+    // Lines of `driver=<driver>`, `label=<key>=<value>` and `opt=<key>=<value>`, like the flags
+    // of `docker volume create`.
+    fn parse_options(&self) -> Result<CreateVolumeOptions<String>, libc::c_int> {
+        let options = self.options.lock().unwrap();
+        let options = std::str::from_utf8(&options).map_err(|_| libc::EINVAL)?;
+
+        let mut create = CreateVolumeOptions {
+            name: self.name.clone(),
+            driver: String::from("local"),
+            driver_opts: HashMap::new(),
+            labels: HashMap::new(),
+        };
+
+        for line in options
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let pair = |value: &str| {
+                value
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            };
+
+            match line.split_once('=') {
+                Some(("driver", driver)) => create.driver = driver.to_string(),
+                Some(("label", label)) => {
+                    let (key, value) = pair(label).unwrap_or((label.to_string(), String::new()));
+                    create.labels.insert(key, value);
+                }
+                Some(("opt", opt)) => {
+                    let (key, value) = pair(opt).ok_or(libc::EINVAL)?;
+                    create.driver_opts.insert(key, value);
+                }
+                _ => {
+                    log::error!("Invalid option for volume {}: {}", self.name, line);
+                    return Err(libc::EINVAL);
+                }
+            }
+        }
+
+        Ok(create)
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Volume, &self.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker_strategy::inode_table::InodeTable;
+
+    fn draft(options: &str) -> DraftVolume {
+        let inodes = Arc::new(Mutex::new(InodeTable::new()));
+        let draft = DraftVolume::new("data", &inodes, &DockerOptions::default());
+        *draft.options.lock().unwrap() = options.as_bytes().to_vec();
+        draft
+    }
+
+    #[test]
+    fn test_parse_options_defaults() {
+        let options = draft("").parse_options().unwrap();
+
+        assert_eq!(options.name, "data");
+        assert_eq!(options.driver, "local");
+        assert!(options.driver_opts.is_empty());
+        assert!(options.labels.is_empty());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = draft(
+            "# tmpfs volume\n\
+             driver=local\n\
+             opt=type=tmpfs\n  opt=o=size=100m\n\n\
+             label=team=web\n\
+             label=backup\n",
+        )
+        .parse_options()
+        .unwrap();
+
+        assert_eq!(options.driver, "local");
+        assert_eq!(options.driver_opts["type"], "tmpfs");
+        assert_eq!(options.driver_opts["o"], "size=100m");
+        assert_eq!(options.labels["team"], "web");
+        assert_eq!(options.labels["backup"], "");
+    }
+
+    #[test]
+    fn test_parse_invalid_options() {
+        assert_eq!(draft("size=1g").parse_options().err(), Some(libc::EINVAL));
+        assert_eq!(draft("opt=tmpfs").parse_options().err(), Some(libc::EINVAL));
+        assert_eq!(draft("driver").parse_options().err(), Some(libc::EINVAL));
+    }
+}
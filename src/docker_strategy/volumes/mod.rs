@@ -0,0 +1,13 @@
+pub(crate) mod draft_volume;
+pub(crate) mod volume;
+pub(crate) mod volume_content;
+pub(crate) mod volume_data;
+pub(crate) mod volume_file;
+pub(crate) mod volume_helper;
+pub(crate) mod volumes_root;
+
+pub(crate) use draft_volume::DraftVolume;
+pub(crate) use volume::Volume;
+pub(crate) use volume_content::{VolumeContent, VolumeUpload};
+pub(crate) use volume_data::VolumeData;
+pub(crate) use volume_file::VolumeFile;
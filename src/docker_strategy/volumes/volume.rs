@@ -0,0 +1,321 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use fuser::{FileAttr, FileType};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::{join_path, ChildDirectory},
+        file_handle::FileHandle,
+        inode_table::{InodeKind, Inodes},
+        parent_directories::ParentDirectories,
+        volumes::{volume_data::VolumeChange, VolumeContent, VolumeFile},
+        DockerOptions,
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+const DATA: &str = "data";
+
+#[derive(Debug)]
+pub struct Volume {
+    ino: u64,
+    pub volume: bollard::service::Volume,
+    content: Arc<VolumeContent>,
+    inodes: Inodes,
+}
+
+impl From<Volume> for Box<dyn ChildDirectory> {
+    fn from(value: Volume) -> Self {
+        Box::new(value)
+    }
+}
+
+impl ChildDirectory for Volume {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn get_id(&self) -> &String {
+        &self.volume.name
+    }
+
+    fn get_name(&self) -> &String {
+        &self.volume.name
+    }
+
+    fn get_parent(&self) -> ParentDirectories {
+        ParentDirectories::Volumes
+    }
+
+    fn as_volume(&self) -> Option<&Volume> {
+        Some(self)
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<Vec<DirectoryEntry>, libc::c_int>> {
+        async move {
+            match Self::data_path(path) {
+                Some(data_path) => self.data_entries(path, data_path).await,
+                None if path.is_empty() => Ok(self.entries()),
+                None => Err(libc::ENOTDIR),
+            }
+        }
+        .boxed()
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        path: &'a str,
+        name: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileAttr, libc::c_int>> {
+        async move {
+            let path = join_path(path, name);
+            if let Some(data_path) = Self::data_path(&path) {
+                if !data_path.is_empty() {
+                    self.content.data().await?;
+                }
+            }
+
+            self.getattr(&path)
+        }
+        .boxed()
+    }
+
+    fn getattr(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        match Self::data_path(path) {
+            _ if path.is_empty() => Ok(self.dir_attr()),
+            Some("") => Ok(FileAttr {
+                ino: self.entry_ino(path),
+                ..self.dir_attr()
+            }),
+            // Files being written report the size of their pending content.
+            Some(data_path) => {
+                let attr = self
+                    .content
+                    .loaded()?
+                    .attr(data_path, self.entry_ino(path))?;
+                match self.content.upload(data_path) {
+                    Some(upload) => Ok(FileAttr {
+                        size: upload.size(),
+                        ..attr
+                    }),
+                    None => Ok(attr),
+                }
+            }
+            None => {
+                let file = VolumeFile::try_from(path).map_err(|_| libc::ENOENT)?;
+
+                Ok(FileAttr {
+                    ino: self.entry_ino(path),
+                    size: file.content(&self.volume)?.len() as u64,
+                    kind: FileType::RegularFile,
+                    perm: 0o444,
+                    nlink: 1,
+                    ..self.dir_attr()
+                })
+            }
+        }
+    }
+
+    fn set_size(&self, path: &str, size: u64) -> Result<FileAttr, libc::c_int> {
+        let data_path = Self::data_entry_path(path).ok_or(libc::EACCES)?;
+
+        match self.content.upload(data_path) {
+            Some(upload) => upload.set_size(size),
+            None => self.content.apply(VolumeChange::Truncate {
+                path: data_path.to_string(),
+                size,
+            })?,
+        }
+
+        self.getattr(path)
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a str,
+        _docker: &'a bollard::Docker,
+    ) -> BoxFuture<'a, Result<FileHandle, libc::c_int>> {
+        async move {
+            if let Some(data_path) = Self::data_path(path) {
+                return self.content.open(data_path).await;
+            }
+
+            let content = VolumeFile::try_from(path)
+                .map_err(|_| libc::ENOENT)?
+                .content(&self.volume)?;
+
+            Ok(FileHandle::Snapshot(Arc::new(content.into_bytes())))
+        }
+        .boxed()
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        let data_path = Self::data_path(path).ok_or(libc::EINVAL)?;
+
+        self.content.loaded()?.readlink(data_path)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), libc::c_int> {
+        let attr = self.getattr(path)?;
+        let data_path = match Self::data_entry_path(path) {
+            Some(data_path) => data_path,
+            None => return Err(libc::EPERM),
+        };
+
+        self.content.apply(VolumeChange::Remove {
+            path: data_path.to_string(),
+            directory: attr.kind == FileType::Directory,
+        })
+    }
+
+    fn create(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        let data_path = Self::data_entry_path(path).ok_or(libc::EACCES)?;
+        self.content
+            .apply(VolumeChange::CreateFile(data_path.to_string()))?;
+
+        self.getattr(path)
+    }
+
+    fn mkdir(&self, path: &str) -> Result<FileAttr, libc::c_int> {
+        let data_path = Self::data_entry_path(path).ok_or(libc::EPERM)?;
+        self.content
+            .apply(VolumeChange::CreateDirectory(data_path.to_string()))?;
+
+        self.getattr(path)
+    }
+
+    fn rename(&self, path: &str, new_path: &str) -> Result<(), libc::c_int> {
+        let (from, to) = match (Self::data_entry_path(path), Self::data_entry_path(new_path)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(libc::EPERM),
+        };
+
+        self.content.apply(VolumeChange::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+        })?;
+        self.inodes
+            .lock()
+            .unwrap()
+            .rename(InodeKind::Volume, &self.volume.name, path, new_path);
+
+        Ok(())
+    }
+}
+
+impl Volume {
+    pub fn new(
+        volume: bollard::service::Volume,
+        docker: &bollard::Docker,
+        inodes: &Inodes,
+        options: &DockerOptions,
+    ) -> Self {
+        Self {
+            ino: inodes.lock().unwrap().pin(InodeKind::Volume, &volume.name),
+            content: Arc::new(VolumeContent::new(docker, &volume, options)),
+            volume,
+            inodes: inodes.clone(),
+        }
+    }
+
+    pub fn keep_state(&mut self, previous: &Volume) {
+        self.content = previous.content.clone();
+    }
+
+    fn data_path(path: &str) -> Option<&str> {
+        match path.split_once('/') {
+            Some((DATA, data_path)) => Some(data_path),
+            None if path == DATA => Some(""),
+            _ => None,
+        }
+    }
+
+    // Entries inside of the data directory, the only paths that can be changed.
+    fn data_entry_path(path: &str) -> Option<&str> {
+        Self::data_path(path).filter(|data_path| !data_path.is_empty())
+    }
+
+    async fn data_entries(
+        &self,
+        path: &str,
+        data_path: &str,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let data = self.content.data().await?;
+
+        let parent_ino = match path.rsplit_once('/') {
+            Some((parent, _)) => self.entry_ino(parent),
+            None => self.ino,
+        };
+
+        let mut entries = vec![
+            DirectoryEntry::new(self.entry_ino(path), FileType::Directory, "."),
+            DirectoryEntry::new(parent_ino, FileType::Directory, ".."),
+        ];
+
+        entries.extend(data.entries(data_path)?.iter().map(|(name, kind)| {
+            DirectoryEntry::new(self.entry_ino(&join_path(path, name)), *kind, name)
+        }));
+
+        Ok(entries)
+    }
+
+    fn entries(&self) -> Vec<DirectoryEntry> {
+        let mut entries = vec![
+            DirectoryEntry::new(self.ino, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Volumes as u64, FileType::Directory, ".."),
+        ];
+
+        entries.extend(VolumeFile::iterator().map(|file| {
+            DirectoryEntry::new(
+                self.entry_ino(file.as_str()),
+                FileType::RegularFile,
+                file.as_str(),
+            )
+        }));
+
+        entries.push(DirectoryEntry::new(
+            self.entry_ino(DATA),
+            FileType::Directory,
+            DATA,
+        ));
+
+        entries
+    }
+
+    fn entry_ino(&self, path: &str) -> u64 {
+        self.inodes
+            .lock()
+            .unwrap()
+            .get_or_allocate(InodeKind::Volume, &self.volume.name, path)
+    }
+
+    pub fn dir_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.ino,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use tokio::runtime::Handle;
+
+use crate::docker_strategy::{
+    file_handle::FileHandle,
+    volumes::{volume_data::VolumeChange, VolumeData},
+    DockerOptions,
+};
+
+// Data of a volume, shared by the refreshes of its directory so that snapshots and files being
+// written survive them.
+#[derive(Debug)]
+pub struct VolumeContent {
+    docker: bollard::Docker,
+    volume: bollard::service::Volume,
+    local_daemon: bool,
+    helper_image: String,
+    cache_dir: PathBuf,
+    data: Mutex<Option<Arc<VolumeData>>>,
+    uploads: Mutex<HashMap<String, Weak<VolumeUpload>>>,
+}
+
+impl VolumeContent {
+    pub fn new(
+        docker: &bollard::Docker,
+        volume: &bollard::service::Volume,
+        options: &DockerOptions,
+    ) -> Self {
+        Self {
+            docker: docker.clone(),
+            volume: volume.clone(),
+            local_daemon: options.local_daemon,
+            helper_image: options.helper_image.clone(),
+            cache_dir: options.cache_dir.join("volumes"),
+            data: Mutex::new(None),
+            uploads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Data as of the last lookup, for the calls that can't wait for a download.
+    pub fn loaded(&self) -> Result<Arc<VolumeData>, libc::c_int> {
+        self.data.lock().unwrap().clone().ok_or(libc::ENOENT)
+    }
+
+    pub async fn data(&self) -> Result<Arc<VolumeData>, libc::c_int> {
+        if let Some(data) = self.data.lock().unwrap().as_ref() {
+            if !data.is_expired() {
+                return Ok(data.clone());
+            }
+        }
+
+        self.reload().await
+    }
+
+    async fn reload(&self) -> Result<Arc<VolumeData>, libc::c_int> {
+        let data = Arc::new(
+            VolumeData::load(
+                &self.docker,
+                &self.volume,
+                self.local_daemon,
+                &self.helper_image,
+                &self.cache_dir,
+            )
+            .await?,
+        );
+        *self.data.lock().unwrap() = Some(data.clone());
+
+        Ok(data)
+    }
+
+    // Handles opened on the same file of a snapshot share its content, so that writes through
+    // one of them aren't lost when another one is flushed.
+    pub async fn open(self: &Arc<Self>, path: &str) -> Result<FileHandle, libc::c_int> {
+        let data = self.data().await?;
+        if let VolumeData::Host(_) = data.as_ref() {
+            return data.open(path);
+        }
+
+        if let Some(upload) = self.upload(path) {
+            return Ok(FileHandle::Upload(upload));
+        }
+
+        let upload = Arc::new(VolumeUpload {
+            path: path.to_string(),
+            mode: data.attr(path, 0)?.perm as u32,
+            content: Mutex::new(data.read(path)?),
+            dirty: AtomicBool::new(false),
+            volume: self.clone(),
+        });
+        self.uploads
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Arc::downgrade(&upload));
+
+        Ok(FileHandle::Upload(upload))
+    }
+
+    pub fn upload(&self, path: &str) -> Option<Arc<VolumeUpload>> {
+        let mut uploads = self.uploads.lock().unwrap();
+        uploads.retain(|_, upload| upload.strong_count() > 0);
+
+        uploads.get(path).and_then(Weak::upgrade)
+    }
+
+    // Snapshots are patched after a change through a helper. They are downloaded again when it
+    // failed, the commands being able to fail halfway, or when they no longer match the volume.
+    pub fn apply(&self, change: VolumeChange) -> Result<(), libc::c_int> {
+        let handle = Handle::current();
+
+        match handle.block_on(self.data())?.as_ref() {
+            VolumeData::Host(root) => change.apply_on_host(root),
+            VolumeData::Snapshot { tree, archive } => {
+                let result = handle.block_on(change.apply_with_helper(
+                    &self.docker,
+                    &self.volume.name,
+                    &self.helper_image,
+                ));
+                let patched = result.is_ok()
+                    && change
+                        .apply_on_snapshot(&mut tree.lock().unwrap(), archive)
+                        .is_ok();
+                if !patched {
+                    handle.block_on(self.reload())?;
+                }
+
+                result
+            }
+        }
+    }
+}
+
+// Content of a file of a volume the host can't reach, uploaded back when one of its handles is
+// flushed after a write.
+#[derive(Debug)]
+pub struct VolumeUpload {
+    path: String,
+    mode: u32,
+    content: Mutex<Vec<u8>>,
+    dirty: AtomicBool,
+    volume: Arc<VolumeContent>,
+}
+
+impl VolumeUpload {
+    pub fn read(&self, offset: i64, size: u32) -> Vec<u8> {
+        let content = self.content.lock().unwrap();
+        let start = (offset.max(0) as usize).min(content.len());
+        let end = (start + size as usize).min(content.len());
+
+        content[start..end].to_vec()
+    }
+
+    pub fn write(&self, offset: i64, data: &[u8]) -> u32 {
+        let mut content = self.content.lock().unwrap();
+        let start = offset.max(0) as usize;
+        if content.len() < start + data.len() {
+            content.resize(start + data.len(), 0);
+        }
+        content[start..start + data.len()].copy_from_slice(data);
+        self.dirty.store(true, Ordering::SeqCst);
+
+        data.len() as u32
+    }
+
+    pub fn size(&self) -> u64 {
+        self.content.lock().unwrap().len() as u64
+    }
+
+    pub fn set_size(&self, size: u64) {
+        self.content.lock().unwrap().resize(size as usize, 0);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    pub fn flush(&self) -> Result<(), libc::c_int> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let change = VolumeChange::Write {
+            path: self.path.clone(),
+            content: self.content.lock().unwrap().clone(),
+            mode: self.mode,
+        };
+
+        self.volume
+            .apply(change)
+            .inspect_err(|_| self.dirty.store(true, Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,617 @@
+use std::{
+    ffi::CString,
+    fs::{File, Metadata},
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::{FileTypeExt, MetadataExt},
+        },
+    },
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use fuser::{FileAttr, FileType};
+
+use crate::docker_strategy::{
+    containers::RootFs,
+    docker::errno_from_io_error,
+    file_handle::FileHandle,
+    volumes::volume_helper::{VolumeHelper, MOUNT},
+};
+
+fn kind_from_file_type(file_type: std::fs::FileType) -> FileType {
+    if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_char_device() {
+        FileType::CharDevice
+    } else if file_type.is_block_device() {
+        FileType::BlockDevice
+    } else if file_type.is_fifo() {
+        FileType::NamedPipe
+    } else if file_type.is_socket() {
+        FileType::Socket
+    } else {
+        FileType::RegularFile
+    }
+}
+
+fn attr_from_metadata(metadata: &Metadata, ino: u64) -> FileAttr {
+    let time = |seconds: i64| UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64);
+
+    FileAttr {
+        ino,
+        size: metadata.size(),
+        blocks: metadata.blocks(),
+        atime: time(metadata.atime()),
+        mtime: time(metadata.mtime()),
+        ctime: time(metadata.ctime()),
+        crtime: time(metadata.mtime()),
+        kind: kind_from_file_type(metadata.file_type()),
+        perm: metadata.mode() as u16 & 0o7777,
+        nlink: metadata.nlink() as u32,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        rdev: metadata.rdev() as u32,
+        flags: 0,
+        blksize: metadata.blksize() as u32,
+    }
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        result => Ok(result),
+    }
+}
+
+fn open_at(dir: RawFd, name: &CString, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let fd = check(unsafe { libc::openat(dir, name.as_ptr(), flags | libc::O_CLOEXEC, 0o644) })?;
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Entry of a volume read in place, resolved from the root of the volume without following
+// symbolic links. Containers can replace any directory of a volume with a link, which would
+// otherwise lead the daemon anywhere on the host.
+struct HostEntry {
+    parent: OwnedFd,
+    name: CString,
+}
+
+impl HostEntry {
+    fn resolve(root: &Path, path: &str) -> io::Result<Self> {
+        let name = |name: &str| match name {
+            ".." => Err(io::Error::from_raw_os_error(libc::EACCES)),
+            name => CString::new(name).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+
+        let root = CString::new(root.as_os_str().as_bytes())?;
+        let mut parent = open_at(libc::AT_FDCWD, &root, libc::O_PATH | libc::O_DIRECTORY)?;
+
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let last = components.pop().unwrap_or(".");
+        for component in components {
+            parent = open_at(
+                parent.as_raw_fd(),
+                &name(component)?,
+                libc::O_PATH | libc::O_DIRECTORY | libc::O_NOFOLLOW,
+            )?;
+        }
+
+        Ok(Self {
+            parent,
+            name: name(last)?,
+        })
+    }
+
+    fn open(&self, flags: libc::c_int) -> io::Result<File> {
+        open_at(
+            self.parent.as_raw_fd(),
+            &self.name,
+            flags | libc::O_NOFOLLOW,
+        )
+        .map(File::from)
+    }
+
+    // Links themselves are opened as paths, and not followed.
+    fn metadata(&self) -> io::Result<Metadata> {
+        self.open(libc::O_PATH)?.metadata()
+    }
+
+    fn read_dir(&self) -> io::Result<std::fs::ReadDir> {
+        let dir = self.open(libc::O_RDONLY | libc::O_DIRECTORY)?;
+        std::fs::read_dir(format!("/proc/self/fd/{}", dir.as_raw_fd()))
+    }
+
+    fn read(&self) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(libc::O_RDONLY)?.read_to_end(&mut content)?;
+
+        Ok(content)
+    }
+
+    fn read_link(&self) -> io::Result<PathBuf> {
+        let mut link = vec![0u8; libc::PATH_MAX as usize];
+        let length = unsafe {
+            libc::readlinkat(
+                self.parent.as_raw_fd(),
+                self.name.as_ptr(),
+                link.as_mut_ptr() as *mut libc::c_char,
+                link.len(),
+            )
+        };
+        if length < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        link.truncate(length as usize);
+
+        Ok(PathBuf::from(std::ffi::OsString::from_vec(link)))
+    }
+
+    fn create_dir(&self) -> io::Result<()> {
+        check(unsafe { libc::mkdirat(self.parent.as_raw_fd(), self.name.as_ptr(), 0o755) })
+            .map(|_| ())
+    }
+
+    fn remove(&self, directory: bool) -> io::Result<()> {
+        let flags = if directory { libc::AT_REMOVEDIR } else { 0 };
+        check(unsafe { libc::unlinkat(self.parent.as_raw_fd(), self.name.as_ptr(), flags) })
+            .map(|_| ())
+    }
+
+    fn rename(&self, to: &HostEntry) -> io::Result<()> {
+        check(unsafe {
+            libc::renameat(
+                self.parent.as_raw_fd(),
+                self.name.as_ptr(),
+                to.parent.as_raw_fd(),
+                to.name.as_ptr(),
+            )
+        })
+        .map(|_| ())
+    }
+}
+
+// Content of a volume, read in place from its mountpoint when the host can, or from an archive
+// downloaded through a helper container otherwise.
+#[derive(Debug)]
+pub enum VolumeData {
+    Host(PathBuf),
+    Snapshot { tree: Mutex<RootFs>, archive: File },
+}
+
+impl VolumeData {
+    // Only volumes of the local driver of a daemon running on this host are read in place, the
+    // same mountpoint on this host being another volume, if any, for other daemons.
+    fn host_mountpoint(volume: &bollard::service::Volume, local_daemon: bool) -> Option<PathBuf> {
+        if !local_daemon || volume.driver != "local" || volume.mountpoint.is_empty() {
+            return None;
+        }
+
+        let mountpoint = PathBuf::from(&volume.mountpoint);
+        std::fs::read_dir(&mountpoint).ok().map(|_| mountpoint)
+    }
+
+    pub async fn load(
+        docker: &bollard::Docker,
+        volume: &bollard::service::Volume,
+        local_daemon: bool,
+        helper_image: &str,
+        cache_dir: &Path,
+    ) -> Result<VolumeData, libc::c_int> {
+        if let Some(mountpoint) = Self::host_mountpoint(volume, local_daemon) {
+            return Ok(VolumeData::Host(mountpoint));
+        }
+
+        let path = cache_dir.join(format!("{}.tar", volume.name));
+        let helper = VolumeHelper::create(docker, &volume.name, helper_image, true).await?;
+        let result = RootFs::download(docker, helper.get_id(), MOUNT, &path).await;
+        helper.remove(docker).await;
+        result?;
+
+        // The archive stays open, a later snapshot replacing the file without changing it.
+        Ok(VolumeData::Snapshot {
+            tree: Mutex::new(
+                RootFs::load_archive(path.clone(), MOUNT.trim_start_matches('/')).await?,
+            ),
+            archive: File::open(&path).map_err(errno_from_io_error)?,
+        })
+    }
+
+    // Snapshots are refreshed like the filesystems of containers, the host being always current.
+    pub fn is_expired(&self) -> bool {
+        match self {
+            VolumeData::Host(_) => false,
+            VolumeData::Snapshot { tree, .. } => tree.lock().unwrap().is_expired(),
+        }
+    }
+
+    pub fn attr(&self, path: &str, ino: u64) -> Result<FileAttr, libc::c_int> {
+        match self {
+            VolumeData::Host(root) => HostEntry::resolve(root, path)
+                .and_then(|entry| entry.metadata())
+                .map(|metadata| attr_from_metadata(&metadata, ino))
+                .map_err(errno_from_io_error),
+            VolumeData::Snapshot { tree, .. } => Ok(tree
+                .lock()
+                .unwrap()
+                .get(path)
+                .ok_or(libc::ENOENT)?
+                .attr(ino)),
+        }
+    }
+
+    pub fn entries(&self, path: &str) -> Result<Vec<(String, FileType)>, libc::c_int> {
+        match self {
+            VolumeData::Host(root) => {
+                let mut entries: Vec<(String, FileType)> = HostEntry::resolve(root, path)
+                    .and_then(|entry| entry.read_dir())
+                    .map_err(errno_from_io_error)?
+                    .filter_map(|entry| {
+                        let entry = entry.ok()?;
+                        let kind = kind_from_file_type(entry.file_type().ok()?);
+                        Some((entry.file_name().into_string().ok()?, kind))
+                    })
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+                Ok(entries)
+            }
+            VolumeData::Snapshot { tree, .. } => {
+                let tree = tree.lock().unwrap();
+                let node = tree.get(path).ok_or(libc::ENOENT)?;
+                if node.kind != FileType::Directory {
+                    return Err(libc::ENOTDIR);
+                }
+
+                Ok(node
+                    .children
+                    .iter()
+                    .map(|(name, kind)| (name.clone(), *kind))
+                    .collect())
+            }
+        }
+    }
+
+    // Files of the host are opened for writing when the host allows it, snapshots only give
+    // their content, written back by the caller.
+    pub fn open(&self, path: &str) -> Result<FileHandle, libc::c_int> {
+        match self {
+            VolumeData::Host(root) => HostEntry::resolve(root, path)
+                .and_then(|entry| {
+                    entry
+                        .open(libc::O_RDWR)
+                        .or_else(|_| entry.open(libc::O_RDONLY))
+                })
+                .map(FileHandle::Disk)
+                .map_err(errno_from_io_error),
+            VolumeData::Snapshot { .. } => Err(libc::EROFS),
+        }
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, libc::c_int> {
+        match self {
+            VolumeData::Host(root) => HostEntry::resolve(root, path)
+                .and_then(|entry| entry.read())
+                .map_err(errno_from_io_error),
+            VolumeData::Snapshot { tree, archive } => {
+                let tree = tree.lock().unwrap();
+                tree.read(archive, path).map_err(errno_from_io_error)
+            }
+        }
+    }
+
+    pub fn readlink(&self, path: &str) -> Result<String, libc::c_int> {
+        match self {
+            VolumeData::Host(root) => HostEntry::resolve(root, path)
+                .and_then(|entry| entry.read_link())
+                .map(|link| link.to_string_lossy().to_string())
+                .map_err(errno_from_io_error),
+            VolumeData::Snapshot { tree, .. } => tree
+                .lock()
+                .unwrap()
+                .get(path)
+                .ok_or(libc::ENOENT)?
+                .link
+                .clone()
+                .ok_or(libc::EINVAL),
+        }
+    }
+}
+
+// Change to the data of a volume, paths being relative to its root. Changes are applied in place
+// when the host can reach the volume, and through helpers otherwise.
+#[derive(Debug)]
+pub enum VolumeChange {
+    CreateFile(String),
+    CreateDirectory(String),
+    Remove {
+        path: String,
+        directory: bool,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Truncate {
+        path: String,
+        size: u64,
+    },
+    Write {
+        path: String,
+        content: Vec<u8>,
+        mode: u32,
+    },
+}
+
+impl VolumeChange {
+    pub fn apply_on_host(&self, root: &Path) -> Result<(), libc::c_int> {
+        let entry = |path: &str| HostEntry::resolve(root, path);
+
+        match self {
+            VolumeChange::CreateFile(path) => entry(path)
+                .and_then(|entry| entry.open(libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL))
+                .map(|_| ()),
+            VolumeChange::CreateDirectory(path) => entry(path).and_then(|entry| entry.create_dir()),
+            VolumeChange::Remove { path, directory } => {
+                entry(path).and_then(|entry| entry.remove(*directory))
+            }
+            VolumeChange::Rename { from, to } => {
+                entry(from).and_then(|from| from.rename(&entry(to)?))
+            }
+            VolumeChange::Truncate { path, size } => entry(path)
+                .and_then(|entry| entry.open(libc::O_WRONLY))
+                .and_then(|file| file.set_len(*size)),
+            VolumeChange::Write { path, content, .. } => entry(path)
+                .and_then(|entry| entry.open(libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC))
+                .and_then(|mut file| file.write_all(content)),
+        }
+        .map_err(errno_from_io_error)
+    }
+
+    // Mirrors a change applied through a helper in the snapshot taken before it.
+    pub fn apply_on_snapshot(&self, tree: &mut RootFs, archive: &File) -> Result<(), libc::c_int> {
+        match self {
+            VolumeChange::CreateFile(path) => tree.insert_file(path, 0o644, Vec::new()),
+            VolumeChange::CreateDirectory(path) => tree.insert_directory(path),
+            VolumeChange::Remove { path, .. } => tree.remove(path),
+            VolumeChange::Rename { from, to } => tree.rename(from, to),
+            VolumeChange::Truncate { path, size } => {
+                let perm = tree.get(path).ok_or(libc::ENOENT)?.perm;
+                let mut content = tree.read(archive, path).map_err(errno_from_io_error)?;
+                content.resize(*size as usize, 0);
+                tree.insert_file(path, perm, content)
+            }
+            VolumeChange::Write {
+                path,
+                content,
+                mode,
+            } => tree.insert_file(path, *mode as u16 & 0o7777, content.clone()),
+        }
+    }
+
+    // Files are created and written by uploading archives, everything else runs commands of the
+    // helper image.
+    pub async fn apply_with_helper(
+        &self,
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+    ) -> Result<(), libc::c_int> {
+        let in_mount = |path: &str| format!("{}/{}", MOUNT, path);
+
+        match self {
+            VolumeChange::CreateFile(path) => {
+                Self::upload(
+                    docker,
+                    volume,
+                    image,
+                    path,
+                    tar::EntryType::Regular,
+                    &[],
+                    0o644,
+                )
+                .await
+            }
+            VolumeChange::CreateDirectory(path) => {
+                Self::upload(
+                    docker,
+                    volume,
+                    image,
+                    path,
+                    tar::EntryType::Directory,
+                    &[],
+                    0o755,
+                )
+                .await
+            }
+            VolumeChange::Write {
+                path,
+                content,
+                mode,
+            } => {
+                Self::upload(
+                    docker,
+                    volume,
+                    image,
+                    path,
+                    tar::EntryType::Regular,
+                    content,
+                    *mode,
+                )
+                .await
+            }
+            VolumeChange::Remove { path, directory } => {
+                let command = match directory {
+                    true => vec!["rmdir", "--"],
+                    false => vec!["rm", "-f", "--"],
+                };
+                VolumeHelper::run(
+                    docker,
+                    volume,
+                    image,
+                    Self::command(&command, &[in_mount(path)]),
+                )
+                .await
+            }
+            VolumeChange::Rename { from, to } => {
+                let paths = [in_mount(from), in_mount(to)];
+                VolumeHelper::run(
+                    docker,
+                    volume,
+                    image,
+                    Self::command(&["mv", "-f", "--"], &paths),
+                )
+                .await
+            }
+            VolumeChange::Truncate { path, size } => {
+                let command = ["truncate", "-s", &size.to_string(), "--"];
+                VolumeHelper::run(
+                    docker,
+                    volume,
+                    image,
+                    Self::command(&command, &[in_mount(path)]),
+                )
+                .await
+            }
+        }
+    }
+
+    fn command(command: &[&str], paths: &[String]) -> Vec<String> {
+        command
+            .iter()
+            .map(|argument| argument.to_string())
+            .chain(paths.iter().cloned())
+            .collect()
+    }
+
+    // Archive of a single entry, extracted into the parent directory of the path.
+    async fn upload(
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+        path: &str,
+        kind: tar::EntryType,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<(), libc::c_int> {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, name, content)
+            .map_err(errno_from_io_error)?;
+        let archive = builder.into_inner().map_err(errno_from_io_error)?;
+
+        VolumeHelper::upload(docker, volume, image, dir, archive).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fuse_docker_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn volume(driver: &str, mountpoint: &Path) -> bollard::service::Volume {
+        bollard::service::Volume {
+            name: String::from("data"),
+            driver: driver.to_string(),
+            mountpoint: mountpoint.display().to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_host_mountpoint() {
+        let mountpoint = temp_dir("mountpoint");
+
+        assert_eq!(
+            VolumeData::host_mountpoint(&volume("local", &mountpoint), true),
+            Some(mountpoint.clone())
+        );
+        // The same path on this host isn't the volume of a remote daemon.
+        assert_eq!(
+            VolumeData::host_mountpoint(&volume("local", &mountpoint), false),
+            None
+        );
+        assert_eq!(
+            VolumeData::host_mountpoint(&volume("nfs", &mountpoint), true),
+            None
+        );
+        assert_eq!(
+            VolumeData::host_mountpoint(&volume("local", &mountpoint.join("missing")), true),
+            None
+        );
+
+        std::fs::remove_dir_all(mountpoint).unwrap();
+    }
+
+    #[test]
+    fn test_host_links_are_not_followed() {
+        let root = temp_dir("links");
+        let outside = temp_dir("outside");
+        std::fs::write(outside.join("secret"), "secret").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::fs::create_dir(root.join("dir")).unwrap();
+        let data = VolumeData::Host(root.clone());
+
+        assert_eq!(data.attr("link", 2).unwrap().kind, FileType::Symlink);
+        assert_eq!(
+            data.readlink("link").unwrap(),
+            outside.display().to_string()
+        );
+        assert!(data.read("link/secret").is_err());
+        assert!(data.read("link").is_err());
+        assert!(data.entries("link").is_err());
+        assert!(data.open("link/secret").is_err());
+        assert_eq!(data.read("dir/../link/secret"), Err(libc::EACCES));
+
+        let write = |path: &str| VolumeChange::Write {
+            path: path.to_string(),
+            content: b"written".to_vec(),
+            mode: 0o644,
+        };
+        assert!(write("link/secret").apply_on_host(&root).is_err());
+        assert!(write("link").apply_on_host(&root).is_err());
+        assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"secret");
+
+        write("dir/file").apply_on_host(&root).unwrap();
+        assert_eq!(data.read("dir/file").unwrap(), b"written");
+        assert_eq!(
+            data.entries("dir").unwrap(),
+            vec![(String::from("file"), FileType::RegularFile)]
+        );
+        VolumeChange::Rename {
+            from: String::from("dir/file"),
+            to: String::from("moved"),
+        }
+        .apply_on_host(&root)
+        .unwrap();
+        assert_eq!(data.read("moved").unwrap(), b"written");
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
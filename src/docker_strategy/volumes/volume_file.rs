@@ -0,0 +1,68 @@
+use crate::docker_strategy::docker_strategy::DockerError;
+
+#[derive(Debug, Clone, Copy)]
+pub enum VolumeFile {
+    Inspect,
+    Labels,
+    Driver,
+    Mountpoint,
+}
+
+impl TryFrom<&str> for VolumeFile {
+    type Error = DockerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "inspect.json" => Ok(VolumeFile::Inspect),
+            "labels" => Ok(VolumeFile::Labels),
+            "driver" => Ok(VolumeFile::Driver),
+            "mountpoint" => Ok(VolumeFile::Mountpoint),
+            _ => Err(DockerError::UnknownChildEntry),
+        }
+    }
+}
+
+impl VolumeFile {
+    pub fn iterator() -> impl Iterator<Item = VolumeFile> {
+        [
+            VolumeFile::Inspect,
+            VolumeFile::Labels,
+            VolumeFile::Driver,
+            VolumeFile::Mountpoint,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VolumeFile::Inspect => "inspect.json",
+            VolumeFile::Labels => "labels",
+            VolumeFile::Driver => "driver",
+            VolumeFile::Mountpoint => "mountpoint",
+        }
+    }
+
+    pub fn content(&self, volume: &bollard::service::Volume) -> Result<String, libc::c_int> {
+        match self {
+            VolumeFile::Inspect => serde_json::to_string_pretty(volume)
+                .map(|inspect| inspect + "\n")
+                .map_err(|e| {
+                    log::error!("Failed to serialize volume: {}", e);
+                    libc::EIO
+                }),
+            VolumeFile::Labels => {
+                let mut labels: Vec<String> = volume
+                    .labels
+                    .iter()
+                    .map(|(key, value)| format!("{}={}\n", key, value))
+                    .collect();
+                labels.sort();
+
+                Ok(labels.concat())
+            }
+            VolumeFile::Driver => Ok(format!("{}\n", volume.driver)),
+            VolumeFile::Mountpoint => Ok(format!("{}\n", volume.mountpoint)),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use bollard::{
+    container::{
+        Config, CreateContainerOptions, RemoveContainerOptions, UploadToContainerOptions,
+        WaitContainerOptions,
+    },
+    image::CreateImageOptions,
+    service::{HostConfig, Mount, MountTypeEnum},
+};
+use futures::TryStreamExt;
+
+use crate::docker_strategy::docker::errno_from_docker_error;
+
+// Where the volume is mounted in the helper.
+pub const MOUNT: &str = "/data";
+
+// Container mounting a volume so that its content can go through the archive endpoints when the
+// volume isn't reachable from the host. Helpers are only run for what the archive endpoints
+// can't do, removing, renaming and truncating files.
+#[derive(Debug)]
+pub struct VolumeHelper {
+    id: String,
+}
+
+impl VolumeHelper {
+    // The helper is never run, images without a command being usable as well.
+    pub async fn create(
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+        read_only: bool,
+    ) -> Result<VolumeHelper, libc::c_int> {
+        Self::create_with(docker, volume, image, read_only, vec![String::from("/")]).await
+    }
+
+    async fn create_with(
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+        read_only: bool,
+        entrypoint: Vec<String>,
+    ) -> Result<VolumeHelper, libc::c_int> {
+        let config = Config {
+            image: Some(image.to_string()),
+            entrypoint: Some(entrypoint),
+            network_disabled: Some(true),
+            host_config: Some(HostConfig {
+                mounts: Some(vec![Mount {
+                    target: Some(MOUNT.to_string()),
+                    source: Some(volume.to_string()),
+                    typ: Some(MountTypeEnum::VOLUME),
+                    read_only: Some(read_only),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let response = match docker
+            .create_container(None::<CreateContainerOptions<String>>, config.clone())
+            .await
+        {
+            // The helper image is pulled the first time it is needed.
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {
+                Self::pull(docker, image).await?;
+                docker
+                    .create_container(None::<CreateContainerOptions<String>>, config)
+                    .await
+            }
+            response => response,
+        }
+        .map_err(|e| {
+            log::error!("Failed to create helper for volume {}: {}", volume, e);
+            errno_from_docker_error(&e)
+        })?;
+
+        Ok(Self { id: response.id })
+    }
+
+    async fn pull(docker: &bollard::Docker, image: &str) -> Result<(), libc::c_int> {
+        docker
+            .create_image(
+                Some(CreateImageOptions {
+                    from_image: image,
+                    ..Default::default()
+                }),
+                None,
+                None,
+            )
+            .try_for_each(|_| async { Ok(()) })
+            .await
+            .map_err(|e| {
+                log::error!("Failed to pull helper image {}: {}", image, e);
+                errno_from_docker_error(&e)
+            })
+    }
+
+    // Extracts an archive into a directory of the volume, relative to its root.
+    pub async fn upload(
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+        dir: &str,
+        archive: Vec<u8>,
+    ) -> Result<(), libc::c_int> {
+        let helper = Self::create(docker, volume, image, false).await?;
+        let path = format!("{}/{}", MOUNT, dir);
+
+        let result = docker
+            .upload_to_container(
+                &helper.id,
+                Some(UploadToContainerOptions {
+                    path: path.as_str(),
+                    ..Default::default()
+                }),
+                archive.into(),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Failed to upload to {} in volume {}: {}", dir, volume, e);
+                errno_from_docker_error(&e)
+            });
+
+        helper.remove(docker).await;
+        result
+    }
+
+    // Runs a command of the helper image on the volume, which only succeeds with a zero exit code.
+    pub async fn run(
+        docker: &bollard::Docker,
+        volume: &str,
+        image: &str,
+        command: Vec<String>,
+    ) -> Result<(), libc::c_int> {
+        let helper = Self::create_with(docker, volume, image, false, command.clone()).await?;
+
+        let result = async {
+            docker.start_container::<String>(&helper.id, None).await?;
+            docker
+                .wait_container(&helper.id, None::<WaitContainerOptions<String>>)
+                .try_for_each(|_| async { Ok(()) })
+                .await
+        }
+        .await
+        .map_err(|e| {
+            log::error!("Failed to run {:?} on volume {}: {}", command, volume, e);
+            errno_from_docker_error(&e)
+        });
+
+        helper.remove(docker).await;
+        result
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn remove(self, docker: &bollard::Docker) {
+        if let Err(e) = docker
+            .remove_container(
+                &self.id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+        {
+            log::error!("Failed to remove helper container {}: {}", self.id, e);
+        }
+    }
+}
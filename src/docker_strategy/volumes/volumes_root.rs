@@ -0,0 +1,178 @@
+use bollard::volume::RemoveVolumeOptions;
+use fuser::{FileAttr, FileType};
+use std::{sync::Arc, time::UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::{
+    docker_strategy::{
+        child_directories::child_directories::ChildDirectory,
+        docker::errno_from_docker_error,
+        parent_directories::ParentDirectories,
+        volumes::{DraftVolume, Volume},
+    },
+    fuse_handler::DirectoryEntry,
+};
+
+impl ParentDirectories {
+    pub(crate) async fn volumes_root_lookup(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_volumes().await {
+            log::error!("Failed to update volumes: {}", e);
+        }
+
+        let volume_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        let attr = docker
+            .get::<Volume>()
+            .into_iter()
+            .map(|volume| volume as &dyn ChildDirectory)
+            .chain(
+                docker
+                    .get::<DraftVolume>()
+                    .into_iter()
+                    .map(|draft| draft as &dyn ChildDirectory),
+            )
+            .find(|volume| volume.get_name() == volume_name)
+            .ok_or(libc::ENOENT)?
+            .getattr("")?;
+        docker.remember(attr.ino);
+
+        Ok(attr)
+    }
+
+    pub(crate) async fn volumes_root_mkdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<FileAttr, libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_volumes().await {
+            log::error!("Failed to update volumes: {}", e);
+        }
+
+        let volume_name = name.to_str().ok_or(libc::EINVAL)?;
+
+        let exists = docker
+            .get::<Volume>()
+            .into_iter()
+            .any(|volume| volume.get_name() == volume_name)
+            || docker
+                .get::<DraftVolume>()
+                .into_iter()
+                .any(|draft| draft.get_name() == volume_name);
+        if exists {
+            return Err(libc::EEXIST);
+        }
+
+        let draft = DraftVolume::new(volume_name, docker.get_inodes(), docker.get_options());
+        let attr = draft.dir_attr();
+        docker.insert_child(draft.into());
+
+        Ok(attr)
+    }
+
+    // Volumes used by containers are kept, docker answering with a conflict.
+    pub(crate) async fn volumes_root_rmdir(
+        name: &std::ffi::OsStr,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<(), libc::c_int> {
+        let mut docker = docker.lock().await;
+        if let Err(e) = docker.update_volumes().await {
+            log::error!("Failed to update volumes: {}", e);
+        }
+
+        let volume_name = name.to_str().ok_or(libc::ENOENT)?;
+
+        // Drafts only exist in the mount until their volume is created.
+        if let Some(ino) = docker
+            .get::<DraftVolume>()
+            .into_iter()
+            .find(|draft| draft.get_name() == volume_name && !draft.is_created())
+            .map(|draft| draft.get_ino())
+        {
+            docker.remove_child(ino);
+            return Ok(());
+        }
+
+        let ino = docker
+            .get::<Volume>()
+            .into_iter()
+            .find(|volume| volume.get_name() == volume_name)
+            .map(|volume| volume.get_ino())
+            .ok_or(libc::ENOENT)?;
+
+        docker
+            .get_docker()
+            .remove_volume(volume_name, None::<RemoveVolumeOptions>)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to remove volume {}: {}", volume_name, e);
+                errno_from_docker_error(&e)
+            })?;
+
+        docker.remove_child(ino);
+        Ok(())
+    }
+
+    pub(crate) fn volumes_root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.into(),
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH, // 1970-01-01 00:00:00
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o777,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            flags: 0,
+            blksize: 0,
+        }
+    }
+
+    pub(crate) async fn volumes_root_read_dir(
+        &self,
+        docker: Arc<Mutex<crate::docker_strategy::Docker>>,
+    ) -> Result<Vec<DirectoryEntry>, libc::c_int> {
+        let mut entries = vec![
+            DirectoryEntry::new(ParentDirectories::Volumes as u64, FileType::Directory, "."),
+            DirectoryEntry::new(ParentDirectories::Root as u64, FileType::Directory, ".."),
+        ];
+
+        let mut docker = docker.lock().await;
+
+        if let Err(error) = docker.update_volumes().await {
+            log::debug!("Failed to update volumes, error: {}", error);
+        }
+
+        docker.get::<Volume>().into_iter().for_each(|volume| {
+            entries.push(DirectoryEntry::new(
+                volume.get_ino(),
+                FileType::Directory,
+                volume.get_name(),
+            ))
+        });
+
+        docker
+            .get::<DraftVolume>()
+            .into_iter()
+            .filter(|draft| !draft.is_created())
+            .for_each(|draft| {
+                entries.push(DirectoryEntry::new(
+                    draft.get_ino(),
+                    FileType::Directory,
+                    draft.get_name(),
+                ))
+            });
+
+        entries[2..].sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entries)
+    }
+}
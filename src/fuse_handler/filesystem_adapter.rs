@@ -1,35 +1,63 @@
-use std::ffi::OsStr;
+use std::{ffi::OsStr, path::Path};
 
-use fuser::Request;
+use fuser::{FileAttr, FileType, Request};
 
-pub trait FileSystemStrategy {
+#[derive(Debug, Clone)]
+pub struct DirectoryEntry {
+    pub ino: u64,
+    pub kind: FileType,
+    pub name: String,
+}
+
+impl DirectoryEntry {
+    pub fn new(ino: u64, kind: FileType, name: &str) -> Self {
+        Self {
+            ino,
+            kind,
+            name: name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OpenedFile {
+    pub fh: u64,
+    pub flags: u32,
+}
+
+// Strategies only return data or an errno, `FuseHandler` answers the kernel with it. `lookup`,
+// `open`, `setattr`, `readdir`, `unlink`, `symlink`, `create`, `rmdir`, `rename`, `read`,
+// `write`, `flush` and `mkdir` may block on downloads, helper containers or containers being
+// stopped, they are called outside of the session loop and don't get the request.
+pub trait FileSystemStrategy: Send + Sync {
     fn init(&self) -> Result<(), libc::c_int>;
-    fn lookup(
+    fn lookup(&self, parent: u64, name: &OsStr) -> Result<FileAttr, libc::c_int>;
+    fn forget(&self, req: &Request<'_>, ino: u64, nlookup: u64);
+    fn open(&self, ino: u64, flags: i32) -> Result<OpenedFile, libc::c_int>;
+    fn getattr(&self, req: &Request<'_>, ino: u64) -> Result<FileAttr, libc::c_int>;
+    fn setattr(&self, ino: u64, size: Option<u64>) -> Result<FileAttr, libc::c_int>;
+    fn readdir(&self, ino: u64, fh: u64) -> Result<Vec<DirectoryEntry>, libc::c_int>;
+    fn unlink(&self, parent: u64, name: &OsStr) -> Result<(), libc::c_int>;
+    fn symlink(&self, parent: u64, name: &OsStr, link: &Path) -> Result<FileAttr, libc::c_int>;
+    fn mkdir(&self, parent: u64, name: &OsStr) -> Result<FileAttr, libc::c_int>;
+    fn create(
         &self,
-        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
-        reply: fuser::ReplyEntry,
-    ) -> Result<(), libc::c_int>;
-    fn open(
-        &self,
-        req: &Request<'_>,
-        ino: u64,
         flags: i32,
-        reply: fuser::ReplyOpen,
-    ) -> Result<(), libc::c_int>;
-    fn getattr(
-        &self,
-        req: &Request<'_>,
-        ino: u64,
-        reply: fuser::ReplyAttr,
-    ) -> Result<(), libc::c_int>;
-    fn readdir(
+    ) -> Result<(FileAttr, OpenedFile), libc::c_int>;
+    fn rmdir(&self, parent: u64, name: &OsStr) -> Result<(), libc::c_int>;
+    fn rename(
         &self,
-        req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        offset: i64,
-        reply: &mut fuser::ReplyDirectory,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
     ) -> Result<(), libc::c_int>;
+    fn readlink(&self, req: &Request<'_>, ino: u64) -> Result<String, libc::c_int>;
+    fn read(&self, ino: u64, fh: u64, offset: i64, size: u32) -> Result<Vec<u8>, libc::c_int>;
+    fn write(&self, ino: u64, fh: u64, offset: i64, data: &[u8]) -> Result<u32, libc::c_int>;
+    fn flush(&self, ino: u64, fh: u64) -> Result<(), libc::c_int>;
+    fn release(&self, req: &Request<'_>, ino: u64, fh: u64) -> Result<(), libc::c_int>;
 }
@@ -1,13 +1,18 @@
+use std::{sync::Arc, time::Duration};
+
 use fuser::{Filesystem, Request};
+use tokio::runtime::Handle;
 
 use super::FileSystemStrategy;
 
+const TTL: Duration = Duration::from_secs(1);
+
 pub struct FuseHandler {
-    handler: Box<dyn FileSystemStrategy>,
+    handler: Arc<dyn FileSystemStrategy>,
 }
 
 impl FuseHandler {
-    pub fn new(handler: Box<dyn FileSystemStrategy>) -> Self {
+    pub fn new(handler: Arc<dyn FileSystemStrategy>) -> Self {
         Self { handler }
     }
 }
@@ -30,28 +35,69 @@ impl Filesystem for FuseHandler {
         name: &std::ffi::OsStr,
         reply: fuser::ReplyEntry,
     ) {
-        log::debug!(
-            "lookup on parent {} with name {}",
-            parent,
-            name.to_str().unwrap()
-        );
+        log::debug!("lookup on parent {} with name {:?}", parent, name);
+
+        // Looking up a file of a filesystem downloads it first, the session loop keeps serving
+        // other requests meanwhile.
+        let handler = self.handler.clone();
+        let name = name.to_os_string();
+        Handle::current().spawn_blocking(move || match handler.lookup(parent, &name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        log::debug!("forget on ino {} with nlookup {}", ino, nlookup);
 
-        // TODO: find a way to handle moved reply when handling errors
-        self.handler.lookup(_req, parent, name, reply).unwrap();
+        self.handler.forget(_req, ino, nlookup);
     }
 
     fn open(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
         log::debug!("open on ino {} ", _ino);
 
-        // TODO: find a way to handle moved reply when handling errors
-        self.handler.open(_req, _ino, _flags, reply).unwrap();
+        let handler = self.handler.clone();
+        Handle::current().spawn_blocking(move || match handler.open(_ino, _flags) {
+            Ok(opened) => reply.opened(opened.fh, opened.flags),
+            Err(e) => reply.error(e),
+        });
     }
 
     fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: fuser::ReplyAttr) {
         log::debug!("getattr on ino {} ", ino);
 
-        // TODO: find a way to handle moved reply when handling errors
-        self.handler.getattr(_req, ino, reply).unwrap();
+        match self.handler.getattr(_req, ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<fuser::TimeOrNow>,
+        _mtime: Option<fuser::TimeOrNow>,
+        _ctime: Option<std::time::SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<std::time::SystemTime>,
+        _chgtime: Option<std::time::SystemTime>,
+        _bkuptime: Option<std::time::SystemTime>,
+        _flags: Option<u32>,
+        reply: fuser::ReplyAttr,
+    ) {
+        log::debug!("setattr on ino {} with size {:?}", ino, size);
+
+        // Truncating files of volumes the host can't reach runs a helper container.
+        let handler = self.handler.clone();
+        Handle::current().spawn_blocking(move || match handler.setattr(ino, size) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        });
     }
 
     fn readdir(
@@ -69,8 +115,248 @@ impl Filesystem for FuseHandler {
             offset
         );
 
-        match self.handler.readdir(_req, ino, fh, offset, &mut reply) {
-            Ok(_) => reply.ok(),
+        let handler = self.handler.clone();
+        Handle::current().spawn_blocking(move || {
+            let entries = match handler.readdir(ino, fh) {
+                Ok(entries) => entries,
+                Err(e) => return reply.error(e),
+            };
+
+            for (i, entry) in entries.iter().enumerate().skip(offset as usize) {
+                if reply.add(entry.ino, i as i64 + 1, entry.kind, &entry.name) {
+                    break;
+                }
+            }
+            reply.ok();
+        });
+    }
+
+    fn unlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        reply: fuser::ReplyEmpty,
+    ) {
+        log::debug!("unlink on parent {} with name {:?}", parent, name);
+
+        // Removing files of volumes runs a helper container, untagging images waits for the
+        // daemon.
+        let handler = self.handler.clone();
+        let name = name.to_os_string();
+        Handle::current().spawn_blocking(move || match handler.unlink(parent, &name) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        link: &std::path::Path,
+        reply: fuser::ReplyEntry,
+    ) {
+        log::debug!(
+            "symlink on parent {} with name {:?} to {:?}",
+            parent,
+            name,
+            link
+        );
+
+        // Connecting a container to a network and tagging an image wait for the daemon.
+        let handler = self.handler.clone();
+        let (name, link) = (name.to_os_string(), link.to_path_buf());
+        Handle::current().spawn_blocking(move || match handler.symlink(parent, &name, &link) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: fuser::ReplyEntry,
+    ) {
+        log::debug!("mkdir on parent {} with name {:?}", parent, name);
+
+        // Creating an image waits for its pull, its progress being readable meanwhile.
+        let handler = self.handler.clone();
+        let name = name.to_os_string();
+        Handle::current().spawn_blocking(move || match handler.mkdir(parent, &name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        _mode: u32,
+        _umask: u32,
+        flags: i32,
+        reply: fuser::ReplyCreate,
+    ) {
+        log::debug!("create on parent {} with name {:?}", parent, name);
+
+        let handler = self.handler.clone();
+        let name = name.to_os_string();
+        Handle::current().spawn_blocking(move || match handler.create(parent, &name, flags) {
+            Ok((attr, opened)) => reply.created(&TTL, &attr, 0, opened.fh, opened.flags),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn rmdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        reply: fuser::ReplyEmpty,
+    ) {
+        log::debug!("rmdir on parent {} with name {:?}", parent, name);
+
+        // Removing a running container waits for it to stop.
+        let handler = self.handler.clone();
+        let name = name.to_os_string();
+        Handle::current().spawn_blocking(move || match handler.rmdir(parent, &name) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &std::ffi::OsStr,
+        newparent: u64,
+        newname: &std::ffi::OsStr,
+        flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        log::debug!(
+            "rename on parent {} with name {:?} to parent {} with name {:?} and flags {}",
+            parent,
+            name,
+            newparent,
+            newname,
+            flags
+        );
+
+        let handler = self.handler.clone();
+        let (name, newname) = (name.to_os_string(), newname.to_os_string());
+        Handle::current().spawn_blocking(move || {
+            match handler.rename(parent, &name, newparent, &newname, flags) {
+                Ok(()) => reply.ok(),
+                Err(e) => reply.error(e),
+            }
+        });
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: fuser::ReplyData) {
+        log::debug!("readlink on ino {} ", ino);
+
+        match self.handler.readlink(_req, ino) {
+            Ok(link) => reply.data(link.as_bytes()),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: fuser::ReplyData,
+    ) {
+        log::debug!(
+            "read on ino {} with fh {}, offset {} and size {}",
+            ino,
+            fh,
+            offset,
+            size
+        );
+
+        // Reads on logs may wait for new lines, don't hold the session loop meanwhile.
+        let handler = self.handler.clone();
+        Handle::current().spawn_blocking(move || match handler.read(ino, fh, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: fuser::ReplyWrite,
+    ) {
+        log::debug!(
+            "write on ino {} with fh {}, offset {} and size {}",
+            ino,
+            fh,
+            offset,
+            data.len()
+        );
+
+        let handler = self.handler.clone();
+        let data = data.to_vec();
+        Handle::current().spawn_blocking(move || match handler.write(ino, fh, offset, &data) {
+            Ok(written) => reply.written(written),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        log::debug!("flush on ino {} with fh {}", ino, fh);
+
+        // Content written to volumes the host can't reach is uploaded on flush.
+        let handler = self.handler.clone();
+        Handle::current().spawn_blocking(move || match handler.flush(ino, fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        });
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        log::debug!("release on ino {} with fh {}", ino, fh);
+
+        match self.handler.release(_req, ino, fh) {
+            Ok(()) => reply.ok(),
             Err(e) => reply.error(e),
         }
     }
@@ -0,0 +1,44 @@
+use std::ffi::OsString;
+
+use fuser::Notifier;
+use tokio::{runtime::Handle, sync::mpsc::UnboundedReceiver};
+
+// Kernel caches a strategy wants dropped. They are sent from their own thread once the request
+// which triggered them has been answered, the kernel may still hold locks until then.
+#[derive(Debug)]
+pub enum Invalidation {
+    Entry { parent: u64, name: OsString },
+    Inode { ino: u64 },
+}
+
+impl Invalidation {
+    fn send(&self, notifier: &Notifier) -> std::io::Result<()> {
+        match self {
+            Invalidation::Entry { parent, name } => notifier.inval_entry(*parent, name),
+            // Attributes and every cached page of the file.
+            Invalidation::Inode { ino } => notifier.inval_inode(*ino, 0, 0),
+        }
+    }
+
+    // Moves the inodes of an invalidation, for strategies nested in another one.
+    pub fn map_inodes(self, map: impl Fn(u64) -> u64) -> Self {
+        match self {
+            Invalidation::Entry { parent, name } => Invalidation::Entry {
+                parent: map(parent),
+                name,
+            },
+            Invalidation::Inode { ino } => Invalidation::Inode { ino: map(ino) },
+        }
+    }
+
+    pub fn forward(mut invalidations: UnboundedReceiver<Invalidation>, notifier: Notifier) {
+        Handle::current().spawn_blocking(move || {
+            while let Some(invalidation) = invalidations.blocking_recv() {
+                // The kernel answers ENOENT for entries it doesn't have in cache.
+                if let Err(e) = invalidation.send(&notifier) {
+                    log::debug!("Failed to send {:?}: {}", invalidation, e);
+                }
+            }
+        });
+    }
+}
@@ -1,5 +1,7 @@
 pub mod filesystem_adapter;
 pub mod fuse_handler;
+pub mod invalidation;
 
-pub use filesystem_adapter::FileSystemStrategy;
+pub use filesystem_adapter::{DirectoryEntry, FileSystemStrategy, OpenedFile};
 pub use fuse_handler::FuseHandler;
+pub use invalidation::Invalidation;
@@ -3,36 +3,426 @@ extern crate sys_mount;
 mod docker_strategy;
 mod fuse_handler;
 
-use docker_strategy::DockerStrategy;
-use fuse_handler::FuseHandler;
+use std::{
+    fs::File,
+    io::{Read, Write},
+    os::fd::{AsRawFd, FromRawFd},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use clap::Parser;
+use docker_strategy::{
+    docker_host::{DockerHost, HostOptions},
+    pods::libpod,
+    DockerOptions, DockerStrategy, EnginesStrategy,
+};
+use fuse_handler::{FileSystemStrategy, FuseHandler, Invalidation};
 use fuser::MountOption;
 use sys_mount::{unmount, UnmountFlags};
 
-#[tokio::main]
-async fn main() {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("debug"));
+/// Mounts a docker daemon, its containers, images, volumes and networks being directories.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Directory to mount the daemon on
+    mountpoint: PathBuf,
+
+    /// Comma separated mount options: ro, allow_other, allow_root, auto_unmount,
+    /// default_permissions, rmdir_force, rmdir_volumes, pull_async, cache_dir=<dir> and
+    /// helper_image=<image>
+    #[arg(short = 'o', value_name = "OPTIONS", value_delimiter = ',')]
+    options: Vec<String>,
+
+    /// Daemon to connect to: unix://<socket>, tcp://<host>:<port> or https://<host>:<port>,
+    /// `DOCKER_HOST` or the current docker context by default
+    #[arg(short = 'H', long)]
+    host: Option<String>,
+
+    /// Docker context to connect to, `DOCKER_CONTEXT` by default
+    #[arg(short, long)]
+    context: Option<String>,
+
+    /// Engine mounted in a directory named after it, as `<name>=<host>` or as the name of a
+    /// docker context. Repeated to mount several engines side by side
+    #[arg(
+        short,
+        long = "engine",
+        value_name = "NAME[=HOST]",
+        conflicts_with_all = ["host", "context"]
+    )]
+    engines: Vec<String>,
+
+    /// Use TLS and verify the daemon on tcp:// hosts
+    #[arg(
+        long,
+        env = "DOCKER_TLS_VERIFY",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    tlsverify: bool,
+
+    /// Certificate of the authority the daemon is verified against, `ca.pem` of
+    /// `DOCKER_CERT_PATH` or `~/.docker` by default
+    #[arg(long, value_name = "FILE")]
+    tlscacert: Option<PathBuf>,
+
+    /// Client certificate, `cert.pem` of `DOCKER_CERT_PATH` or `~/.docker` by default
+    #[arg(long, value_name = "FILE")]
+    tlscert: Option<PathBuf>,
+
+    /// Client key, `key.pem` of `DOCKER_CERT_PATH` or `~/.docker` by default
+    #[arg(long, value_name = "FILE")]
+    tlskey: Option<PathBuf>,
+
+    /// Log filter, a level like `info` or `debug`, or `env_logger` directives
+    #[arg(long, env = "RUST_LOG", default_value = "info")]
+    log_level: String,
+
+    /// Mount podman, found in `CONTAINER_HOST` or its rootless or rootful socket by default, and
+    /// list its pods. Along with engines, lists the pods of those that are podman
+    #[arg(long)]
+    podman: bool,
+
+    /// Stay attached to the terminal instead of running in the background
+    #[arg(short, long)]
+    foreground: bool,
+
+    /// File the logs are appended to once running in the background, where they are dropped
+    /// otherwise
+    #[arg(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+}
+
+impl Cli {
+    fn host_options(&self) -> HostOptions {
+        HostOptions {
+            host: self.host.clone(),
+            context: self.context.clone(),
+            tls_verify: self.tlsverify,
+            tls_ca_cert: self.tlscacert.clone(),
+            tls_cert: self.tlscert.clone(),
+            tls_key: self.tlskey.clone(),
+            podman: self.podman,
+        }
+    }
+
+    // Engines mounted side by side, none of them having a name when a single engine is mounted
+    // at the root.
+    fn engines(&self) -> Result<Vec<(String, HostOptions)>, String> {
+        if self.engines.is_empty() {
+            return Ok(vec![(String::new(), self.host_options())]);
+        }
+
+        let mut engines: Vec<(String, HostOptions)> = Vec::new();
+        for engine in &self.engines {
+            let (name, host) = match engine.split_once('=') {
+                Some((name, host)) => (name, Some(host.to_string())),
+                None => (engine.as_str(), None),
+            };
+
+            if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                return Err(format!("Invalid engine name {}", name));
+            }
+            if engines.iter().any(|(other, _)| other == name) {
+                return Err(format!("Engine {} is given twice", name));
+            }
+
+            let context = match host {
+                Some(_) => None,
+                None => Some(name.to_string()),
+            };
+            engines.push((
+                name.to_string(),
+                HostOptions {
+                    host,
+                    context,
+                    ..self.host_options()
+                },
+            ));
+        }
+
+        Ok(engines)
+    }
+}
+
+fn describe_engine(name: &str) -> String {
+    match name {
+        "" => String::from("docker daemon"),
+        name => format!("engine {}", name),
+    }
+}
+
+// Options handled by FUSE itself, the others being given to the docker strategy.
+fn mount_options(options: &[String]) -> (Vec<MountOption>, Vec<String>) {
+    let mut mount_options = vec![MountOption::FSName("docker_fuse".to_string())];
+    let mut docker_options = Vec::new();
+
+    for option in options.iter().filter(|option| !option.is_empty()) {
+        match option.as_str() {
+            "ro" => mount_options.push(MountOption::RO),
+            "rw" => mount_options.push(MountOption::RW),
+            "allow_other" => mount_options.push(MountOption::AllowOther),
+            "allow_root" => mount_options.push(MountOption::AllowRoot),
+            "auto_unmount" => mount_options.push(MountOption::AutoUnmount),
+            "default_permissions" => mount_options.push(MountOption::DefaultPermissions),
+            _ => docker_options.push(option.clone()),
+        }
+    }
+
+    if !mount_options.contains(&MountOption::RO) && !mount_options.contains(&MountOption::RW) {
+        mount_options.push(MountOption::RW);
+    }
+
+    (mount_options, docker_options)
+}
+
+// Runs in the background like FUSE daemons do. It must happen before the runtime starts its
+// threads, only the forking thread surviving in the child. The parent waits for the child to be
+// mounted and exits with its outcome, the child reporting its failures on the terminal until
+// then. Returns the pipe to signal the parent with.
+fn daemonize() -> std::io::Result<File> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let (mut reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error()),
+        0 => {}
+        _ => {
+            // The pipe is closed without a byte when the child fails or dies.
+            drop(writer);
+            let mounted = matches!(reader.read(&mut [0]), Ok(1));
+            std::process::exit(if mounted { 0 } else { 1 });
+        }
+    }
+
+    drop(reader);
+    if unsafe { libc::setsid() } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    std::env::set_current_dir("/")?;
 
+    Ok(writer)
+}
+
+// Detaches from the terminal once mounted, logs going to the log file from then on, and lets the
+// parent exit.
+fn detach(mut ready: File, log_file: Option<&File>) -> std::io::Result<()> {
+    let null = File::options().read(true).write(true).open("/dev/null")?;
+    let output = log_file.unwrap_or(&null);
+
+    for (file, fd) in [
+        (&null, libc::STDIN_FILENO),
+        (output, libc::STDOUT_FILENO),
+        (output, libc::STDERR_FILENO),
+    ] {
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    ready.write_all(&[1])
+}
+
+// Reached once before detaching, so that a daemon out of reach fails the command. The runtime
+// is dropped along with the client before forking.
+fn ping(host: &DockerHost) -> Result<(), bollard::errors::Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let docker = host.connect()?;
+
+    runtime.block_on(docker.ping())?;
+    Ok(())
+}
+
+// Address pods are listed from. A single engine mounted as podman must answer the API of podman,
+// while only the engines answering it list pods when several are mounted.
+fn podman_address(host: &DockerHost, detect: bool) -> std::io::Result<Option<String>> {
+    let address = match host.libpod_address() {
+        Ok(address) => address,
+        Err(_) if detect => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    match runtime.block_on(libpod::ping(address)) {
+        Ok(()) => Ok(Some(address.to_string())),
+        Err(_) if detect => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn mount(
+    mountpoint: PathBuf,
+    mount_options: Vec<MountOption>,
+    mut engines: Vec<(String, bollard::Docker, DockerOptions)>,
+    mounted: impl FnOnce() -> std::io::Result<()> + Send + 'static,
+) -> std::io::Result<()> {
     tokio::task::spawn_blocking(move || {
-        ctrlc::set_handler(|| {
-            log::info!("Unmounting /tmp/fuse");
-            unmount("/tmp/fuse", UnmountFlags::DETACH).unwrap();
+        // Both SIGINT and SIGTERM unmount, a killed daemon leaving a dead mount otherwise.
+        let unmounted = mountpoint.clone();
+        ctrlc::set_handler(move || {
+            log::info!("Unmounting {}", unmounted.display());
+            if let Err(e) = unmount(&unmounted, UnmountFlags::DETACH) {
+                log::error!("Failed to unmount {}: {}", unmounted.display(), e);
+            }
         })
-        .unwrap();
-
-        let mountpoint = "/tmp/fuse";
-
-        let strategy = DockerStrategy::new();
-        let handler = FuseHandler::new(Box::new(strategy));
-        fuser::mount2(
-            handler,
-            mountpoint,
-            &[
-                MountOption::RW,
-                MountOption::FSName("docker_fuse".to_string()),
-            ],
-        )
+        .map_err(std::io::Error::other)?;
+
+        let (invalidations, invalidations_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let strategy: Arc<dyn FileSystemStrategy> = if engines.len() == 1 && engines[0].0.is_empty()
+        {
+            let (_, docker, options) = engines.remove(0);
+            Arc::new(DockerStrategy::new(docker, options, invalidations))
+        } else {
+            Arc::new(EnginesStrategy::new(engines, invalidations))
+        };
+        let handler = FuseHandler::new(strategy);
+        let mut session = fuser::Session::new(handler, Path::new(&mountpoint), &mount_options)?;
+        mounted()?;
+
+        Invalidation::forward(invalidations_receiver, session.notifier());
+        session.run()
     })
-    .await
-    .unwrap()
-    .unwrap()
+    .await?
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    env_logger::Builder::new()
+        .parse_filters(&cli.log_level)
+        .init();
+
+    // The mountpoint is resolved before daemonizing moves to `/`.
+    let mountpoint = match cli.mountpoint.canonicalize() {
+        Ok(mountpoint) => mountpoint,
+        Err(e) => {
+            log::error!("Invalid mountpoint {}: {}", cli.mountpoint.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (mount_options, docker_options) = mount_options(&cli.options);
+    let docker_options = DockerOptions::from_mount_options(&docker_options);
+
+    let engines = match cli.engines() {
+        Ok(engines) => engines,
+        Err(e) => {
+            log::error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut hosts = Vec::new();
+    for (name, options) in engines {
+        let host = match DockerHost::resolve(&options) {
+            Ok(host) => host,
+            Err(e) => {
+                log::error!("Failed to find the {}: {}", describe_engine(&name), e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if let Err(e) = ping(&host) {
+            let engine = describe_engine(&name);
+            log::error!("Failed to connect to the {} at {}: {}", engine, host, e);
+            return ExitCode::FAILURE;
+        }
+        log::info!("Connected to the {} at {}", describe_engine(&name), host);
+
+        let podman = match cli.podman {
+            true => podman_address(&host, !cli.engines.is_empty()),
+            false => Ok(None),
+        };
+        let podman = match podman {
+            Ok(podman) => podman,
+            Err(e) => {
+                let engine = describe_engine(&name);
+                log::error!("Failed to list pods of the {} at {}: {}", engine, host, e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        hosts.push((name, host, podman));
+    }
+
+    let log_file = match &cli.log_file {
+        Some(path) => match File::options().create(true).append(true).open(path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                log::error!("Failed to open the log file {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let ready = if cli.foreground {
+        None
+    } else {
+        match daemonize() {
+            Ok(ready) => Some(ready),
+            Err(e) => {
+                log::error!("Failed to run in the background: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to start the runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut engines = Vec::new();
+    for (name, host, podman) in hosts {
+        match host.connect() {
+            Ok(docker) => {
+                // Each engine exports images and volumes in a directory of its own.
+                let options = DockerOptions {
+                    cache_dir: docker_options.cache_dir.join(&name),
+                    local_daemon: host.is_local(),
+                    podman,
+                    ..docker_options.clone()
+                };
+                engines.push((name, docker, options));
+            }
+            Err(e) => {
+                let engine = describe_engine(&name);
+                log::error!("Failed to connect to the {} at {}: {}", engine, host, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // The parent keeps waiting until failures are logged, the pipe being held until then.
+    let signal = match ready.as_ref().map(File::try_clone).transpose() {
+        Ok(signal) => signal,
+        Err(e) => {
+            log::error!("Failed to run in the background: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mounted = move || match signal {
+        Some(signal) => detach(signal, log_file.as_ref()),
+        None => Ok(()),
+    };
+
+    match runtime.block_on(mount(mountpoint, mount_options, engines, mounted)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("Failed to mount: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }